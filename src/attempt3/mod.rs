@@ -1,11 +1,11 @@
 use egg::{define_language, Applier, EGraph, ENode, Id, Metadata, Var};
-use log::{debug, info, trace};
+use log::{debug, info, trace, warn};
 use std::collections::HashMap;
 
 pub mod rewrites;
 
 type DomainIdValue = u32;
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum DomainId {
     Complement(Box<DomainId>),
     DomainId(DomainIdValue),
@@ -18,6 +18,55 @@ pub enum Domain {
     Long(DomainId),
 }
 
+/// Parses whitespace-separated domains in compact notation, e.g.
+/// `"t0^ 1 2* 3"`, as attempt4::parse_domains() does. attempt3 only has
+/// numbered domains, so named ones are an error, and its domains have no
+/// lengths, so any which are given are dropped.
+pub fn parse_domains(notation: &str) -> Result<Vec<Domain>, String> {
+    fn convert(domain_id: &crate::attempt4::DomainId) -> Result<DomainId, String> {
+        match domain_id {
+            crate::attempt4::DomainId::Complement(domain_id) => {
+                Ok(DomainId::Complement(Box::new(convert(domain_id)?)))
+            }
+            crate::attempt4::DomainId::DomainId(v) => Ok(DomainId::DomainId(*v)),
+            crate::attempt4::DomainId::Named(name) => Err(format!(
+                "Couldn't parse domain '{}': domains are numbered",
+                name
+            )),
+        }
+    }
+    crate::attempt4::parse_domains(notation)?
+        .iter()
+        .map(|domain: &crate::attempt4::Domain| match domain {
+            crate::attempt4::Domain::Toehold(domain_id, _) => {
+                Ok(Domain::Toehold(convert(domain_id)?))
+            }
+            crate::attempt4::Domain::Long(domain_id, _) => Ok(Domain::Long(convert(domain_id)?)),
+        })
+        .collect()
+}
+
+/// The inverse of parse_domains().
+pub fn print_domains(domains: &[Domain]) -> String {
+    fn convert(domain_id: &DomainId) -> crate::attempt4::DomainId {
+        match domain_id {
+            DomainId::Complement(domain_id) => {
+                crate::attempt4::DomainId::Complement(Box::new(convert(domain_id)))
+            }
+            DomainId::DomainId(v) => crate::attempt4::DomainId::DomainId(*v),
+        }
+    }
+    crate::attempt4::print_domains(
+        &domains
+            .iter()
+            .map(|domain: &Domain| match domain {
+                Domain::Toehold(domain_id) => crate::attempt4::Domain::toehold(convert(domain_id)),
+                Domain::Long(domain_id) => crate::attempt4::Domain::long(convert(domain_id)),
+            })
+            .collect::<Vec<crate::attempt4::Domain>>(),
+    )
+}
+
 #[derive(Copy, Clone)]
 pub enum TopOrBottom {
     Top,
@@ -71,21 +120,64 @@ define_language! {
 pub struct Meta {
     /// If this eclass contains a domain id, this will hold its value.
     domain_id: Option<DomainId>,
+    /// Pairs of domain ids which disagreed when their eclasses were merged,
+    /// sorted; the first (smaller) of each was kept.
+    conflicts: Vec<(DomainId, DomainId)>,
+}
+impl Meta {
+    pub fn conflicts(&self) -> &[(DomainId, DomainId)] {
+        &self.conflicts
+    }
 }
 impl Metadata<Language> for Meta {
     type Error = ();
 
+    /// A missing domain id takes on the other eclass's. Domain ids which
+    /// disagree are recorded as a conflict rather than aborting, and the
+    /// smaller is kept, so that merging is commutative.
     fn merge(&self, other: &Self) -> Self {
-        assert_eq!(self, other);
-        self.clone()
+        let mut conflicts: Vec<(DomainId, DomainId)> = self
+            .conflicts
+            .iter()
+            .chain(other.conflicts.iter())
+            .cloned()
+            .collect();
+
+        let domain_id: Option<DomainId> = match (&self.domain_id, &other.domain_id) {
+            (None, domain_id) | (domain_id, None) => domain_id.clone(),
+            (Some(a), Some(b)) => {
+                if a != b {
+                    warn!(
+                        "Merged eclasses with conflicting domain ids {:?} and {:?}",
+                        a, b
+                    );
+                    conflicts.push((std::cmp::min(a, b).clone(), std::cmp::max(a, b).clone()));
+                }
+                Some(std::cmp::min(a, b).clone())
+            }
+        };
+        conflicts.sort();
+        conflicts.dedup();
+
+        Meta {
+            domain_id: domain_id,
+            conflicts: conflicts,
+        }
     }
 
     fn make(egraph: &EGraph<Language, Self>, enode: &ENode<Language>) -> Self {
         match &enode.op {
-            Language::BottomDoubleStrandCell => Meta { domain_id: None },
-            Language::TopDoubleStrandCell => Meta { domain_id: None },
+            Language::BottomDoubleStrandCell => Meta {
+                domain_id: None,
+                conflicts: vec![],
+            },
+            Language::TopDoubleStrandCell => Meta {
+                domain_id: None,
+                conflicts: vec![],
+            },
             Language::DomainIdValue(id) => Meta {
                 domain_id: Some(DomainId::DomainId(*id)),
+                conflicts: vec![],
             },
             Language::BottomStrandCell => {
                 assert_eq!(enode.children.len(), 2);
@@ -98,6 +190,7 @@ impl Metadata<Language> for Meta {
                             .unwrap()
                             .clone(),
                     ),
+                    conflicts: vec![],
                 }
             }
             Language::TopStrandCell => {
@@ -111,9 +204,10 @@ impl Metadata<Language> for Meta {
                             .unwrap()
                             .clone(),
                     ),
+                    conflicts: vec![],
                 }
             }
-            //Language::Strand => Meta { domain_id: None },
+            //Language::Strand => Meta { domain_id: None, conflicts: vec![] },
             Language::LongDomain => {
                 assert_eq!(enode.children.len(), 1);
                 Meta {
@@ -125,6 +219,7 @@ impl Metadata<Language> for Meta {
                             .unwrap()
                             .clone(),
                     ),
+                    conflicts: vec![],
                 }
             }
             Language::ToeholdDomain => {
@@ -138,6 +233,7 @@ impl Metadata<Language> for Meta {
                             .unwrap()
                             .clone(),
                     ),
+                    conflicts: vec![],
                 }
             }
             Language::DomainId => {
@@ -151,6 +247,7 @@ impl Metadata<Language> for Meta {
                             .unwrap()
                             .clone(),
                     ),
+                    conflicts: vec![],
                 }
             }
             Language::Complement => {
@@ -174,10 +271,17 @@ impl Metadata<Language> for Meta {
                             }
                         },
                     ),
+                    conflicts: vec![],
                 }
             }
-            Language::Nil => Meta { domain_id: None },
-            Language::StrandId(_) => Meta { domain_id: None },
+            Language::Nil => Meta {
+                domain_id: None,
+                conflicts: vec![],
+            },
+            Language::StrandId(_) => Meta {
+                domain_id: None,
+                conflicts: vec![],
+            },
         }
     }
 }
@@ -316,4 +420,49 @@ mod tests {
             1
         );
     }
+
+    #[test]
+    fn compact_notation() {
+        let domains: Vec<Domain> = parse_domains("t0^ 1 2* 3").unwrap();
+        assert_eq!(print_domains(&domains), "t0^ 1 2* 3");
+        assert!(parse_domains("0 ^").is_err());
+        assert!(parse_domains("t0^ x").is_err());
+
+        let mut egraph = EGraph::<Language, Meta>::default();
+        add_directional_strand_to_egraph(&mut egraph, TopOrBottom::Bottom, &domains);
+        assert_eq!(
+            "(bottom-strand-cell (toehold-domain (domain-id 0))
+              (bottom-strand-cell (long-domain (domain-id 1))
+               (bottom-strand-cell (long-domain (complement (domain-id 2)))
+                (bottom-strand-cell (long-domain (domain-id 3)) nil))))"
+                .parse::<Pattern<_>>()
+                .unwrap()
+                .search(&egraph)
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn merge_metadata() {
+        let meta = |domain_id: Option<DomainIdValue>| Meta {
+            domain_id: domain_id.map(DomainId::DomainId),
+            conflicts: vec![],
+        };
+
+        // A missing domain id takes on the other's.
+        assert_eq!(meta(None).merge(&meta(Some(1))), meta(Some(1)));
+        assert_eq!(meta(Some(1)).merge(&meta(None)), meta(Some(1)));
+
+        // Conflicts are recorded the same way whichever side is merged into.
+        let merged: Meta = meta(Some(2)).merge(&meta(Some(1)));
+        assert_eq!(merged, meta(Some(1)).merge(&meta(Some(2))));
+        assert_eq!(merged.domain_id, Some(DomainId::DomainId(1)));
+        assert_eq!(
+            merged.conflicts(),
+            &[(DomainId::DomainId(1), DomainId::DomainId(2))]
+        );
+        assert_eq!(merged.merge(&meta(Some(0))), meta(Some(0)).merge(&merged));
+        assert_eq!(merged.merge(&meta(Some(0))).conflicts().len(), 2);
+    }
 }
@@ -0,0 +1,279 @@
+use super::enumerate::{reactions, species, Reaction};
+use super::*;
+
+/// A name for a species, made up from its domains, which is safe to use as an
+/// identifier in CRN text: e.g. `t0_d1_0` for the strand `t0^ 1*`, or
+/// `complex_ds_t0` for a double strand bound over `t0^`. Different species get
+/// different names. Anything other than a lone strand is prefixed with one of
+/// Language's own words, which no domain can be named.
+pub fn species_name(value: &Value) -> String {
+    /// Domain names have their underscores doubled, so that they can be told
+    /// apart from the underscores between domains, and complements are
+    /// followed by `_0`, which no name can be.
+    fn domain_name(domain: &Domain) -> String {
+        let domain_id: &DomainId = match domain {
+            Domain::Toehold(domain_id, _) | Domain::Long(domain_id, _) => domain_id,
+        };
+        let name: String = domain.name().replace('_', "__");
+        if domain_id.is_complement() {
+            format!("{}_0", name)
+        } else {
+            name
+        }
+    }
+    fn domains_name(domains: &[Domain]) -> String {
+        if domains.is_empty() {
+            "nil".to_string()
+        } else {
+            domains
+                .iter()
+                .map(domain_name)
+                .collect::<Vec<String>>()
+                .join("_")
+        }
+    }
+
+    match value {
+        Value::StrandCellValue(domains) => domains_name(domains),
+        Value::StrandValue { id, domains } => format!("strand_{}_{}", id, domains_name(domains)),
+        Value::DoubleStrandCellValue(bottom) => format!("complex_ds_{}", domains_name(bottom)),
+        // A free strand is named after its domains.
+        Value::ComplexValue { strands, pairings } if strands.len() == 1 && pairings.is_empty() => {
+            domains_name(&strands[0])
+        }
+        // The number of strands, then each strand's name with its underscores
+        // doubled again, so that the single underscores between them stand
+        // out, then the pairings.
+        Value::ComplexValue { strands, pairings } => format!(
+            "complex_{}_{}{}",
+            strands.len(),
+            strands
+                .iter()
+                .map(|strand: &Vec<Domain>| domains_name(strand).replace('_', "__"))
+                .collect::<Vec<String>>()
+                .join("_"),
+            pairings
+                .iter()
+                .map(|pairing: &Pairing| format!(
+                    "_p{}_{}_{}_{}_{}",
+                    pairing.bottom.strand,
+                    pairing.bottom.domain,
+                    pairing.top.strand,
+                    pairing.top.domain,
+                    pairing.domains.len()
+                ))
+                .collect::<String>()
+        ),
+        Value::DomainIdValue(_)
+        | Value::DomainValue(_)
+        | Value::StrandIdValue(_)
+        | Value::LengthValue(_)
+        | Value::LocationValue(_)
+        | Value::PairingValue(_)
+        | Value::RuleNameValue(_)
+        | Value::ReactionSideValue(_)
+        | Value::ReactionValue { .. } => {
+            panic!("{:?} isn't a species", value)
+        }
+    }
+}
+
+/// The domain-level structure of a species, roughly in Visual DSD notation:
+/// `<...>` for a free strand, `[...]` for a double strand (given by its bottom
+/// strand), and `0:1[...]1:0` for a pairing in a complex, between its
+/// locations. Domains are in compact notation (see parse_domains()), so long
+/// domain 1 is written `1` rather than `d1`, as it is in species names.
+pub fn species_structure(value: &Value) -> String {
+    fn domains_structure(domains: &[Domain]) -> String {
+        domains
+            .iter()
+            .map(Domain::to_string)
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    match value {
+        Value::StrandCellValue(domains) | Value::StrandValue { domains, .. } => {
+            format!("<{}>", domains_structure(domains))
+        }
+        Value::DoubleStrandCellValue(bottom) => format!("[{}]", domains_structure(bottom)),
+        Value::ComplexValue { strands, pairings } => strands
+            .iter()
+            .map(|strand: &Vec<Domain>| format!("<{}>", domains_structure(strand)))
+            .chain(pairings.iter().map(|pairing: &Pairing| {
+                format!(
+                    "{}[{}]{}",
+                    pairing.bottom,
+                    domains_structure(&pairing.domains),
+                    pairing.top
+                )
+            }))
+            .collect::<Vec<String>>()
+            .join(" | "),
+        Value::DomainIdValue(_)
+        | Value::DomainValue(_)
+        | Value::StrandIdValue(_)
+        | Value::LengthValue(_)
+        | Value::LocationValue(_)
+        | Value::PairingValue(_)
+        | Value::RuleNameValue(_)
+        | Value::ReactionSideValue(_)
+        | Value::ReactionValue { .. } => {
+            panic!("{:?} isn't a species", value)
+        }
+    }
+}
+
+/// Writes the reactions found in a saturated `egraph` (see
+/// enumerate::reactions()) out as a plain-text chemical reaction network, one
+/// `A + B -> C + D` reaction per line. A header of comments gives the
+/// structure of each species.
+pub fn to_crn(egraph: &EGraph<Language, Meta>) -> String {
+    let reactions: Vec<Reaction> = reactions(egraph, None);
+    let mut out = String::default();
+
+    out.push_str("# Species\n");
+    for value in species(&reactions) {
+        out.push_str(&format!(
+            "# {} = {}\n",
+            species_name(&value),
+            species_structure(&value)
+        ));
+    }
+
+    out.push_str("\n# Reactions\n");
+    for reaction in reactions.iter() {
+        let side = |values: &Vec<Value>| {
+            values
+                .iter()
+                .map(species_name)
+                .collect::<Vec<String>>()
+                .join(" + ")
+        };
+        out.push_str(&format!(
+            "{} -> {}\n",
+            side(&reaction.reactants),
+            side(&reaction.products)
+        ));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toehold_crn() {
+        use super::super::enumerate::enumerate_in;
+        use super::super::rewrites::{bind, toehold_bind};
+
+        let mut egraph = EGraph::<Language, Meta>::default();
+        add_strand_to_egraph(&mut egraph, &parse_domains("t0^ 1").unwrap()).unwrap();
+        add_strand_to_egraph(&mut egraph, &parse_domains("1* t0^*").unwrap()).unwrap();
+        enumerate_in(&mut egraph, &[], &[toehold_bind(), bind()], None).unwrap();
+
+        assert_eq!(
+            to_crn(&egraph),
+            "# Species
+# t0_d1 = <t0^ 1>
+# complex_2_t0__d1_d1__0__t0__0_p0_0_1_0_2 = <t0^ 1> | <1* t0^*> | 0:0[t0^ 1]1:0
+# complex_2_t0__d1_d1__0__t0__0_p0_0_1_1_1 = <t0^ 1> | <1* t0^*> | 0:0[t0^]1:1
+# d1_0_t0_0 = <1* t0^*>
+
+# Reactions
+t0_d1 + d1_0_t0_0 -> complex_2_t0__d1_d1__0__t0__0_p0_0_1_1_1
+complex_2_t0__d1_d1__0__t0__0_p0_0_1_1_1 -> complex_2_t0__d1_d1__0__t0__0_p0_0_1_0_2
+"
+        );
+    }
+
+    #[test]
+    fn distinct_names() {
+        let named = |name: &str| Domain::long(DomainId::Named(Name::new(name)));
+        let x: Domain = named("x");
+        let strand = |domains: Vec<Domain>| Value::ComplexValue {
+            strands: vec![domains],
+            pairings: vec![],
+        };
+        let values: Vec<Value> = vec![
+            strand(vec![x.complement()]),
+            strand(vec![named("xs")]),
+            strand(vec![named("x_0")]),
+            strand(vec![named("x"), named("y")]),
+            strand(vec![named("x_y")]),
+            strand(vec![Domain::long(DomainId::DomainId(1))]),
+            strand(vec![named("d1")]),
+            strand(vec![named("ds"), x.clone()]),
+            strand(vec![x.clone(), named("copy1")]),
+            strand(vec![named("complex_x")]),
+            Value::DoubleStrandCellValue(vec![x.clone()]),
+            Value::StrandValue {
+                id: 1,
+                domains: vec![x.clone()],
+            },
+            // Strands whose names have underscores, against more strands.
+            Value::ComplexValue {
+                strands: vec![vec![named("x_")], vec![named("y")]],
+                pairings: vec![],
+            },
+            Value::ComplexValue {
+                strands: vec![vec![named("x")], vec![named("y_")]],
+                pairings: vec![],
+            },
+            Value::ComplexValue {
+                strands: vec![vec![named("x"), named("y")], vec![named("z")]],
+                pairings: vec![],
+            },
+            Value::ComplexValue {
+                strands: vec![vec![named("x")], vec![named("y"), named("z")]],
+                pairings: vec![],
+            },
+            Value::ComplexValue {
+                strands: vec![vec![named("x")], vec![named("y")], vec![named("z")]],
+                pairings: vec![],
+            },
+        ];
+        let names: Vec<String> = values.iter().map(species_name).collect();
+        for (i, name) in names.iter().enumerate() {
+            assert!(!names[i + 1..].contains(name), "{} appears twice", name);
+        }
+    }
+
+    #[test]
+    fn structures() {
+        // Structures use compact notation, unlike species names: numbered
+        // long domains are bare numbers.
+        let value = Value::StrandCellValue(parse_domains("t0^ 1* rep_out").unwrap());
+        assert_eq!(species_structure(&value), "<t0^ 1* rep_out>");
+        assert_eq!(species_name(&value), "t0_d1_0_rep__out");
+        let value = Value::ComplexValue {
+            strands: vec![
+                parse_domains("t0^ rep_out").unwrap(),
+                parse_domains("t0^*").unwrap(),
+            ],
+            pairings: vec![Pairing {
+                bottom: Location {
+                    strand: 0,
+                    domain: 0,
+                },
+                top: Location {
+                    strand: 1,
+                    domain: 0,
+                },
+                domains: parse_domains("t0^").unwrap(),
+            }],
+        };
+        assert_eq!(
+            species_structure(&value),
+            "<t0^ rep_out> | <t0^*> | 0:0[t0^]1:0"
+        );
+        assert_eq!(
+            species_name(&value),
+            "complex_2_t0__rep____out_t0__0_p0_0_1_0_1"
+        );
+        let value = Value::DoubleStrandCellValue(parse_domains("t0^ 1").unwrap());
+        assert_eq!(species_structure(&value), "[t0^ 1]");
+    }
+}
@@ -0,0 +1,140 @@
+//! Checking sequence-level crosstalk which the domain-level model can't see.
+//!
+//! Everything else in this crate assumes that a domain only ever binds its
+//! complement. Once domains have sequences, that's no longer guaranteed: two
+//! unrelated domains can share a long enough complementary stretch to bind
+//! each other, which is a leak path the enumerator will never find.
+
+use super::sequence::{longest_complementarity, SequenceAssignment};
+use super::*;
+
+/// A pair of domains which weren't designed to bind, but which have a
+/// complementary stretch of `length` nucleotides between them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Crosstalk {
+    /// The strands the domains are on, as returned by add_strand_to_egraph().
+    /// These are the same strand when the domains could bind within a strand.
+    pub strands: (Id, Id),
+    /// The positions of the domains within their strands.
+    pub positions: (usize, usize),
+    pub domains: (Domain, Domain),
+    pub length: usize,
+}
+
+/// Checks every pair of domains on the strands `strand_ids` (each as returned
+/// by add_strand_to_egraph()) for complementary stretches of at least
+/// `min_length` nucleotides, skipping pairs whose DomainIds are complements of
+/// each other. Stretches spanning the boundary between two domains aren't
+/// considered.
+///
+/// Fails if any of the domains hasn't been given a sequence.
+pub fn find_crosstalk(
+    egraph: &EGraph<Language, Meta>,
+    strand_ids: &[Id],
+    sequences: &SequenceAssignment,
+    min_length: usize,
+) -> Result<Vec<Crosstalk>, String> {
+    let strands: Vec<Vec<(Domain, String)>> = strand_ids
+        .iter()
+        .map(|id: &Id| match &egraph[*id].metadata.value {
+            Some(Value::StrandCellValue(domains)) => domains
+                .iter()
+                .map(|domain: &Domain| match sequences.domain_sequence(domain) {
+                    Some(sequence) => Ok((domain.clone(), sequence)),
+                    None => Err(format!("{} has no sequence", domain)),
+                })
+                .collect(),
+            _ => Err(format!("eclass {} isn't a strand", id)),
+        })
+        .collect::<Result<_, String>>()?;
+
+    let mut crosstalk: Vec<Crosstalk> = Vec::default();
+    for i in 0..strands.len() {
+        for j in i..strands.len() {
+            for (p, (a, a_sequence)) in strands[i].iter().enumerate() {
+                for (q, (b, b_sequence)) in strands[j].iter().enumerate() {
+                    if (i == j && q <= p) || designed_to_bind(a, b) {
+                        continue;
+                    }
+                    let length: usize = longest_complementarity(a_sequence, b_sequence);
+                    if length >= min_length {
+                        crosstalk.push(Crosstalk {
+                            strands: (strand_ids[i], strand_ids[j]),
+                            positions: (p, q),
+                            domains: (a.clone(), b.clone()),
+                            length: length,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(crosstalk)
+}
+
+/// Whether `a` and `b` are complements of each other once any double
+/// complements are collapsed.
+fn designed_to_bind(a: &Domain, b: &Domain) -> bool {
+    fn collapse(domain_id: &DomainId) -> (DomainId, bool) {
+        match domain_id {
+            DomainId::Complement(domain_id) => {
+                let (v, complemented) = collapse(domain_id);
+                (v, !complemented)
+            }
+            DomainId::DomainId(_) | DomainId::Named(_) => (domain_id.clone(), false),
+        }
+    }
+    match (a, b) {
+        (Domain::Toehold(a, _), Domain::Toehold(b, _))
+        | (Domain::Long(a, _), Domain::Long(b, _)) => {
+            let (a, a_complemented) = collapse(a);
+            let (b, b_complemented) = collapse(b);
+            a == b && a_complemented != b_complemented
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leak_between_unrelated_domains() {
+        let mut egraph = EGraph::<Language, Meta>::default();
+        let input: Id =
+            add_strand_to_egraph(&mut egraph, &parse_domains("t0^ 1").unwrap()).unwrap();
+        let gate: Id =
+            add_strand_to_egraph(&mut egraph, &parse_domains("2 t0^*").unwrap()).unwrap();
+
+        let mut sequences = SequenceAssignment::default();
+        sequences.set(&DomainId::DomainId(0), "TCTCCA").unwrap();
+        sequences
+            .set(&DomainId::DomainId(1), "ACATTCCTAAGTCTGAAACA")
+            .unwrap();
+        // Shares the reverse complement of the first eleven bases of domain 1.
+        sequences
+            .set(&DomainId::DomainId(2), "CTTAGGAATGTCCACATCAT")
+            .unwrap();
+
+        let crosstalk: Vec<Crosstalk> =
+            find_crosstalk(&egraph, &[input, gate], &sequences, 8).unwrap();
+        assert_eq!(
+            crosstalk,
+            vec![Crosstalk {
+                strands: (input, gate),
+                positions: (1, 0),
+                domains: (
+                    Domain::long(DomainId::DomainId(1)),
+                    Domain::long(DomainId::DomainId(2))
+                ),
+                length: 11,
+            }]
+        );
+
+        sequences = SequenceAssignment::default();
+        sequences.set(&DomainId::DomainId(0), "TCTCCA").unwrap();
+        assert!(find_crosstalk(&egraph, &[input, gate], &sequences, 8).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+//! Sequence design: choosing nucleotide sequences for a system's domains.
+
+use super::sequence::{longest_common_substring, longest_complementarity, SequenceAssignment};
+use super::*;
+use log::debug;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+const NUCLEOTIDES: [char; 4] = ['A', 'C', 'G', 'T'];
+
+/// Settings for design_sequences().
+#[derive(Debug, Clone, PartialEq)]
+pub struct DesignOptions {
+    /// The fraction of each domain which must be G or C.
+    pub min_gc_content: f64,
+    pub max_gc_content: f64,
+    /// The longest run of a single base allowed within a domain.
+    pub max_run_length: usize,
+    /// The longest stretch of unintended complementarity allowed between two
+    /// domains which aren't complements of each other (including a domain and
+    /// itself, and a domain and the complement of another domain).
+    pub max_crosstalk: usize,
+    /// Gives up after trying this many mutations.
+    pub max_iterations: usize,
+    /// Seed for the random number generator; runs with the same seed give the
+    /// same designs, on any platform and with any version of rand.
+    pub seed: u64,
+}
+
+impl Default for DesignOptions {
+    fn default() -> Self {
+        DesignOptions {
+            min_gc_content: 0.3,
+            max_gc_content: 0.7,
+            max_run_length: 3,
+            max_crosstalk: 4,
+            max_iterations: 100_000,
+            seed: 0,
+        }
+    }
+}
+
+/// A design which didn't meet every constraint before design_sequences() ran
+/// out of iterations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DesignError {
+    /// The best sequences found, which may still be good enough to use.
+    pub best: SequenceAssignment,
+    /// Their design_cost(), which is more than zero.
+    pub cost: f64,
+}
+
+impl std::fmt::Display for DesignError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Design didn't converge; the best design found has cost {}",
+            self.cost
+        )
+    }
+}
+
+impl std::error::Error for DesignError {}
+
+/// Designs a sequence for the id of each of `domains`, as long as the domain.
+///
+/// Starting from random sequences, this repeatedly mutates a single base,
+/// keeping the mutation unless it makes design_cost() worse, until the cost
+/// reaches zero or `options.max_iterations` runs out, in which case it
+/// returns a DesignError holding the best design found. Runs of one base are
+/// only checked within domains, as the order domains will be joined in isn't
+/// known here.
+pub fn design_sequences(
+    domains: &[Domain],
+    options: &DesignOptions,
+) -> Result<SequenceAssignment, DesignError> {
+    let mut ids: Vec<(DomainId, usize)> = Vec::default();
+    for domain in domains {
+        let domain_id: DomainId = match domain {
+            Domain::Toehold(domain_id, _) | Domain::Long(domain_id, _) => domain_id.base(),
+        };
+        if !ids.iter().any(|(id, _)| *id == domain_id) {
+            ids.push((domain_id, domain.length()));
+        }
+    }
+
+    let mut rng = ChaCha8Rng::seed_from_u64(options.seed);
+    let mut sequences: Vec<Vec<char>> = ids
+        .iter()
+        .map(|(_, length)| {
+            (0..*length)
+                .map(|_| NUCLEOTIDES[rng.gen_range(0, NUCLEOTIDES.len())])
+                .collect()
+        })
+        .collect();
+    let mut cost: f64 = sequences_cost(&sequences, options);
+
+    for iteration in 0..options.max_iterations {
+        if cost == 0.0 {
+            debug!("Design converged after {} iterations", iteration);
+            break;
+        }
+        let i: usize = rng.gen_range(0, sequences.len());
+        if sequences[i].is_empty() {
+            continue;
+        }
+        let position: usize = rng.gen_range(0, sequences[i].len());
+        let old: char = sequences[i][position];
+        sequences[i][position] = NUCLEOTIDES[rng.gen_range(0, NUCLEOTIDES.len())];
+
+        let new_cost: f64 = sequences_cost(&sequences, options);
+        if new_cost <= cost {
+            cost = new_cost;
+        } else {
+            sequences[i][position] = old;
+        }
+    }
+
+    let mut assignment = SequenceAssignment::default();
+    for ((domain_id, _), sequence) in ids.iter().zip(sequences.iter()) {
+        assignment
+            .set(domain_id, &sequence.iter().collect::<String>())
+            .unwrap();
+    }
+    if cost > 0.0 {
+        return Err(DesignError {
+            best: assignment,
+            cost: cost,
+        });
+    }
+    Ok(assignment)
+}
+
+/// How far the sequences in `assignment` are from meeting `options`: zero if
+/// every constraint is met, and larger the more they're violated.
+pub fn design_cost(assignment: &SequenceAssignment, options: &DesignOptions) -> f64 {
+    let sequences: Vec<Vec<char>> = assignment
+        .domain_ids()
+        .iter()
+        .map(|domain_id: &DomainId| {
+            assignment
+                .domain_id_sequence(domain_id)
+                .unwrap()
+                .chars()
+                .collect()
+        })
+        .collect();
+    sequences_cost(&sequences, options)
+}
+
+fn sequences_cost(sequences: &[Vec<char>], options: &DesignOptions) -> f64 {
+    let strings: Vec<String> = sequences
+        .iter()
+        .map(|sequence: &Vec<char>| sequence.iter().collect())
+        .collect();
+    let mut cost: f64 = 0.0;
+
+    for sequence in sequences {
+        if sequence.is_empty() {
+            continue;
+        }
+        let gc: f64 = sequence
+            .iter()
+            .filter(|c: &&char| **c == 'G' || **c == 'C')
+            .count() as f64
+            / sequence.len() as f64;
+        if gc < options.min_gc_content {
+            cost += (options.min_gc_content - gc) * sequence.len() as f64;
+        } else if gc > options.max_gc_content {
+            cost += (gc - options.max_gc_content) * sequence.len() as f64;
+        }
+
+        let mut run: usize = 1;
+        for i in 1..sequence.len() {
+            run = if sequence[i] == sequence[i - 1] {
+                run + 1
+            } else {
+                1
+            };
+            if run > options.max_run_length {
+                cost += 1.0;
+            }
+        }
+    }
+
+    let excess = |length: usize| -> f64 {
+        if length > options.max_crosstalk {
+            ((length - options.max_crosstalk) as f64).powi(2)
+        } else {
+            0.0
+        }
+    };
+    for i in 0..strings.len() {
+        for j in i..strings.len() {
+            // i pairing with j*, which is only intended when they're the same
+            // domain...
+            if i != j {
+                cost += excess(longest_common_substring(&strings[i], &strings[j]));
+            }
+            // ...and i pairing with j, which never is.
+            cost += excess(longest_complementarity(&strings[i], &strings[j]));
+        }
+    }
+
+    cost
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn design() {
+        let domains: Vec<Domain> = parse_domains("t0^ 1 2 t3^ 1*").unwrap();
+        let options = DesignOptions::default();
+        let sequences: SequenceAssignment = design_sequences(&domains, &options).unwrap();
+
+        assert_eq!(
+            sequences.domain_ids(),
+            (0..4).map(DomainId::DomainId).collect::<Vec<DomainId>>()
+        );
+        assert_eq!(design_cost(&sequences, &options), 0.0);
+        for domain in &domains {
+            let sequence: String = sequences.domain_sequence(domain).unwrap();
+            assert_eq!(sequence.len(), domain.length());
+            let gc: usize = sequence.chars().filter(|c| *c == 'G' || *c == 'C').count();
+            assert!(gc as f64 >= 0.3 * sequence.len() as f64);
+            assert!(gc as f64 <= 0.7 * sequence.len() as f64);
+            for run in &["AAAA", "CCCC", "GGGG", "TTTT"] {
+                assert!(!sequence.contains(run));
+            }
+        }
+
+        // The same seed gives the same design.
+        assert_eq!(design_sequences(&domains, &options), Ok(sequences));
+
+        // No 6 or 20 base sequences avoid every two base stretch of
+        // crosstalk, so this can't converge, and the best design found says
+        // so.
+        let impossible = DesignOptions {
+            max_crosstalk: 1,
+            max_iterations: 1000,
+            ..DesignOptions::default()
+        };
+        let error: DesignError = design_sequences(&domains, &impossible).unwrap_err();
+        assert!(error.cost > 0.0);
+        assert_eq!(design_cost(&error.best, &impossible), error.cost);
+    }
+}
@@ -0,0 +1,752 @@
+//! A parser for the core of the Visual DSD language: strands (`<t^ x>`,
+//! `{t^*}`), gates made of duplex segments (`{t^*}[x]<y>`, joined with `:` or
+//! `::`), parallel composition (`( ... | ... )`), multiplicity (`10 * P`),
+//! `new` and `def`.
+//!
+//! Upper strands are read left to right, and lower strands right to left, so
+//! that each strand's value runs in its own direction; a duplex `[S]` becomes
+//! a double strand whose bottom strand is `S`, on the upper strand.
+
+use super::*;
+use std::collections::HashMap;
+
+/// Where and why parsing failed. Lines and columns count from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// What parse_dsd() added to the egraph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedSystem {
+    /// The id given to each domain name.
+    pub domain_ids: HashMap<String, DomainId>,
+    /// Each species in the system, as a complex (a lone strand is a complex
+    /// of one strand), along with how many copies of it there are.
+    pub species: Vec<(Id, usize)>,
+}
+
+/// Parses `source` and adds the species it describes to `egraph`.
+pub fn parse_dsd(
+    egraph: &mut EGraph<Language, Meta>,
+    source: &str,
+) -> Result<ParsedSystem, ParseError> {
+    let tokens: Vec<Token> = lex(source)?;
+    let mut parser = Parser {
+        tokens: tokens,
+        position: 0,
+        definitions: HashMap::default(),
+        domain_ids: HashMap::default(),
+        toeholds: HashMap::default(),
+    };
+
+    let process: Option<Process> = parser.parse_program()?;
+    let mut species: Vec<(Id, usize)> = Vec::default();
+    if let Some(process) = process {
+        parser.instantiate(egraph, &process, 1, &mut species, &mut Vec::default())?;
+    }
+    egraph.rebuild();
+
+    Ok(ParsedSystem {
+        domain_ids: parser.domain_ids,
+        species: species,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Identifier(String),
+    Number(String),
+    /// Any punctuation, including `::`.
+    Symbol(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize,
+}
+
+fn lex(source: &str) -> Result<Vec<Token>, ParseError> {
+    const SYMBOLS: [&str; 17] = [
+        "::", "<", ">", "{", "}", "[", "]", "(", ")", "|", ":", "^", "*", "=", "@", ",", ";",
+    ];
+
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens: Vec<Token> = Vec::default();
+    let (mut i, mut line, mut column): (usize, usize, usize) = (0, 1, 1);
+    let advance = |i: &mut usize, line: &mut usize, column: &mut usize| {
+        if chars[*i] == '\n' {
+            *line += 1;
+            *column = 1;
+        } else {
+            *column += 1;
+        }
+        *i += 1;
+    };
+
+    while i < chars.len() {
+        let (start_line, start_column): (usize, usize) = (line, column);
+        let c: char = chars[i];
+        if c.is_whitespace() {
+            advance(&mut i, &mut line, &mut column);
+        } else if c == '(' && chars.get(i + 1) == Some(&'*') {
+            // Comments: (* ... *)
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&')')) {
+                advance(&mut i, &mut line, &mut column);
+            }
+            if i >= chars.len() {
+                return Err(ParseError {
+                    line: start_line,
+                    column: start_column,
+                    message: "Unterminated comment".to_string(),
+                });
+            }
+            advance(&mut i, &mut line, &mut column);
+            advance(&mut i, &mut line, &mut column);
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut text = String::default();
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.')
+            {
+                text.push(chars[i]);
+                advance(&mut i, &mut line, &mut column);
+            }
+            tokens.push(Token {
+                kind: if text.chars().all(|c: char| c.is_ascii_digit() || c == '.') {
+                    TokenKind::Number(text)
+                } else {
+                    TokenKind::Identifier(text)
+                },
+                line: start_line,
+                column: start_column,
+            });
+        } else {
+            let symbol: &'static str = *SYMBOLS
+                .iter()
+                .find(|symbol: &&&str| {
+                    symbol
+                        .chars()
+                        .enumerate()
+                        .all(|(j, s): (usize, char)| chars.get(i + j) == Some(&s))
+                })
+                .ok_or_else(|| ParseError {
+                    line: start_line,
+                    column: start_column,
+                    message: format!("Unexpected character '{}'", c),
+                })?;
+            for _ in 0..symbol.len() {
+                advance(&mut i, &mut line, &mut column);
+            }
+            tokens.push(Token {
+                kind: TokenKind::Symbol(symbol),
+                line: start_line,
+                column: start_column,
+            });
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// One segment of a gate: `{lower_left}<upper_left>[duplex]<upper_right>{lower_right}`.
+/// Lower strand domains are kept left to right, as written.
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Segment {
+    lower_left: Vec<Domain>,
+    upper_left: Vec<Domain>,
+    duplex: Option<Vec<Domain>>,
+    upper_right: Vec<Domain>,
+    lower_right: Vec<Domain>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Process {
+    Species {
+        segments: Vec<Segment>,
+        /// Whether each segment after the first is joined to the previous one
+        /// along its lower strand (`:`) or its upper strand (`::`).
+        joins: Vec<&'static str>,
+        /// Where the species starts.
+        line: usize,
+        column: usize,
+    },
+    Parallel(Vec<Process>),
+    Copies(usize, Box<Process>),
+    Reference {
+        name: String,
+        /// Where the reference is.
+        line: usize,
+        column: usize,
+    },
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    definitions: HashMap<String, Process>,
+    domain_ids: HashMap<String, DomainId>,
+    /// Whether each domain name is a toehold.
+    toeholds: HashMap<String, bool>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens
+            .get(self.position)
+            .map(|token: &Token| &token.kind)
+    }
+
+    fn peek_symbol(&self, symbol: &str) -> bool {
+        match self.peek() {
+            Some(TokenKind::Symbol(s)) => *s == symbol,
+            _ => false,
+        }
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(TokenKind::Identifier(s)) => s == keyword,
+            _ => false,
+        }
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        let (line, column): (usize, usize) = match self.tokens.get(self.position) {
+            Some(token) => (token.line, token.column),
+            None => match self.tokens.last() {
+                Some(token) => (token.line, token.column + 1),
+                None => (1, 1),
+            },
+        };
+        ParseError {
+            line: line,
+            column: column,
+            message: message,
+        }
+    }
+
+    fn describe_next(&self) -> String {
+        match self.peek() {
+            None => "end of input".to_string(),
+            Some(TokenKind::Identifier(s)) | Some(TokenKind::Number(s)) => format!("'{}'", s),
+            Some(TokenKind::Symbol(s)) => format!("'{}'", s),
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), ParseError> {
+        if self.peek_symbol(symbol) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!(
+                "Expected '{}', found {}",
+                symbol,
+                self.describe_next()
+            )))
+        }
+    }
+
+    fn expect_identifier(&mut self) -> Result<String, ParseError> {
+        match self.peek().cloned() {
+            Some(TokenKind::Identifier(s)) => {
+                self.position += 1;
+                Ok(s)
+            }
+            _ => Err(self.error(format!("Expected a name, found {}", self.describe_next()))),
+        }
+    }
+
+    /// program: [def | directive]* [process]
+    fn parse_program(&mut self) -> Result<Option<Process>, ParseError> {
+        loop {
+            if self.peek_keyword("def") {
+                self.position += 1;
+                let name: String = self.expect_identifier()?;
+                // We don't support parameters, but allow empty parameter lists.
+                if self.peek_symbol("(") {
+                    self.position += 1;
+                    self.expect_symbol(")")?;
+                }
+                self.expect_symbol("=")?;
+                let body: Process = self.parse_process()?;
+                self.definitions.insert(name, body);
+            } else if self.peek_keyword("directive") {
+                // Directives configure simulation; skip to the end of the line.
+                let line: usize = self.tokens[self.position].line;
+                while self.position < self.tokens.len() && self.tokens[self.position].line == line {
+                    self.position += 1;
+                }
+            } else {
+                break;
+            }
+        }
+
+        if self.position == self.tokens.len() {
+            return Ok(None);
+        }
+        let process: Process = self.parse_process()?;
+        if self.position != self.tokens.len() {
+            return Err(self.error(format!("Unexpected {}", self.describe_next())));
+        }
+        Ok(Some(process))
+    }
+
+    /// process: term ['|' term]*
+    fn parse_process(&mut self) -> Result<Process, ParseError> {
+        let mut terms: Vec<Process> = vec![self.parse_term()?];
+        while self.peek_symbol("|") {
+            self.position += 1;
+            terms.push(self.parse_term()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.pop().unwrap()
+        } else {
+            Process::Parallel(terms)
+        })
+    }
+
+    /// term: '(' process ')' | 'new' name ['@' num ',' num] process
+    ///       | num '*' term | name | species
+    fn parse_term(&mut self) -> Result<Process, ParseError> {
+        match self.peek().cloned() {
+            Some(TokenKind::Symbol("(")) => {
+                self.position += 1;
+                let process: Process = self.parse_process()?;
+                self.expect_symbol(")")?;
+                Ok(process)
+            }
+            Some(TokenKind::Identifier(ref keyword)) if keyword == "new" => {
+                self.position += 1;
+                self.expect_identifier()?;
+                if self.peek_symbol("@") {
+                    self.position += 1;
+                    self.expect_number()?;
+                    self.expect_symbol(",")?;
+                    self.expect_number()?;
+                }
+                self.parse_process()
+            }
+            Some(TokenKind::Number(_)) => {
+                let count: String = self.expect_number()?;
+                let count: usize = count
+                    .parse()
+                    .map_err(|_| self.error("Expected a whole number of copies".to_string()))?;
+                self.expect_symbol("*")?;
+                Ok(Process::Copies(count, Box::new(self.parse_term()?)))
+            }
+            Some(TokenKind::Identifier(name)) => {
+                let token: &Token = &self.tokens[self.position];
+                let (line, column): (usize, usize) = (token.line, token.column);
+                self.position += 1;
+                Ok(Process::Reference {
+                    name: name,
+                    line: line,
+                    column: column,
+                })
+            }
+            _ => self.parse_species(),
+        }
+    }
+
+    fn expect_number(&mut self) -> Result<String, ParseError> {
+        match self.peek().cloned() {
+            Some(TokenKind::Number(s)) => {
+                self.position += 1;
+                Ok(s)
+            }
+            _ => Err(self.error(format!("Expected a number, found {}", self.describe_next()))),
+        }
+    }
+
+    /// species: segment [(':' | '::') segment]*
+    fn parse_species(&mut self) -> Result<Process, ParseError> {
+        let (line, column): (usize, usize) = match self.tokens.get(self.position) {
+            Some(token) => (token.line, token.column),
+            None => (0, 0),
+        };
+        let mut segments: Vec<Segment> = vec![self.parse_segment()?];
+        let mut joins: Vec<&'static str> = Vec::default();
+        loop {
+            let join: &'static str = if self.peek_symbol("::") {
+                "::"
+            } else if self.peek_symbol(":") {
+                ":"
+            } else {
+                break;
+            };
+            self.position += 1;
+            joins.push(join);
+            segments.push(self.parse_segment()?);
+        }
+
+        if segments
+            .iter()
+            .any(|segment: &Segment| segment.duplex.is_none())
+        {
+            let is_single_strand: bool = segments.len() == 1
+                && (segments[0].upper_left.is_empty() || segments[0].lower_left.is_empty());
+            if !is_single_strand {
+                return Err(
+                    self.error("Expected a duplex '[...]' in every segment of a gate".to_string())
+                );
+            }
+        }
+        Ok(Process::Species {
+            segments: segments,
+            joins: joins,
+            line: line,
+            column: column,
+        })
+    }
+
+    /// segment: ['{' domains '}'] ['<' domains '>'] ['[' domains ']'
+    ///          ['<' domains '>'] ['{' domains '}']]
+    fn parse_segment(&mut self) -> Result<Segment, ParseError> {
+        let start: usize = self.position;
+        let mut segment = Segment::default();
+        if self.peek_symbol("{") {
+            segment.lower_left = self.parse_domains("{", "}")?;
+        }
+        if self.peek_symbol("<") {
+            segment.upper_left = self.parse_domains("<", ">")?;
+        }
+        if self.peek_symbol("[") {
+            segment.duplex = Some(self.parse_domains("[", "]")?);
+            if self.peek_symbol("<") {
+                segment.upper_right = self.parse_domains("<", ">")?;
+            }
+            if self.peek_symbol("{") {
+                segment.lower_right = self.parse_domains("{", "}")?;
+            }
+        }
+        if self.position == start {
+            return Err(self.error(format!(
+                "Expected a strand or gate, found {}",
+                self.describe_next()
+            )));
+        }
+        Ok(segment)
+    }
+
+    /// domains: open [name ['^'] ['*']]* close
+    fn parse_domains(&mut self, open: &str, close: &str) -> Result<Vec<Domain>, ParseError> {
+        self.expect_symbol(open)?;
+        let mut domains: Vec<Domain> = Vec::default();
+        while !self.peek_symbol(close) {
+            let name: String = match self.peek().cloned() {
+                Some(TokenKind::Identifier(s)) | Some(TokenKind::Number(s)) => {
+                    if s.parse::<DomainIdValue>().is_err() && !symbol::is_valid_name(&s) {
+                        return Err(self.error(format!("'{}' can't name a domain", s)));
+                    }
+                    self.position += 1;
+                    s
+                }
+                _ => {
+                    return Err(self.error(format!(
+                        "Expected a domain or '{}', found {}",
+                        close,
+                        self.describe_next()
+                    )))
+                }
+            };
+            let is_toehold: bool = self.peek_symbol("^");
+            if is_toehold {
+                self.position += 1;
+            }
+            let is_complement: bool = self.peek_symbol("*");
+            if is_complement {
+                self.position += 1;
+            }
+
+            match self.toeholds.get(&name) {
+                Some(was_toehold) if *was_toehold != is_toehold => {
+                    self.position -= 1 + is_toehold as usize + is_complement as usize;
+                    return Err(self.error(format!(
+                        "Domain {} is used both as a toehold and as a long domain",
+                        name
+                    )));
+                }
+                _ => {
+                    self.toeholds.insert(name.clone(), is_toehold);
+                }
+            }
+            let domain_id: DomainId = self
+                .domain_ids
+                .entry(name.clone())
+                .or_insert_with(|| match name.parse::<DomainIdValue>() {
+                    Ok(v) => DomainId::DomainId(v),
+                    Err(_) => DomainId::Named(Name::new(&name)),
+                })
+                .clone();
+            let domain_id: DomainId = if is_complement {
+                domain_id.complement()
+            } else {
+                domain_id
+            };
+            domains.push(if is_toehold {
+                Domain::toehold(domain_id)
+            } else {
+                Domain::long(domain_id)
+            });
+        }
+        self.expect_symbol(close)?;
+        Ok(domains)
+    }
+
+    fn instantiate(
+        &self,
+        egraph: &mut EGraph<Language, Meta>,
+        process: &Process,
+        copies: usize,
+        species: &mut Vec<(Id, usize)>,
+        expanding: &mut Vec<String>,
+    ) -> Result<(), ParseError> {
+        match process {
+            Process::Parallel(processes) => {
+                for process in processes {
+                    self.instantiate(egraph, process, copies, species, expanding)?;
+                }
+            }
+            Process::Copies(count, process) => {
+                self.instantiate(egraph, process, copies * count, species, expanding)?;
+            }
+            Process::Reference { name, line, column } => {
+                let error = |message: String| ParseError {
+                    line: *line,
+                    column: *column,
+                    message: message,
+                };
+                if expanding.contains(name) {
+                    return Err(error(format!("{} is defined in terms of itself", name)));
+                }
+                let definition: &Process = self
+                    .definitions
+                    .get(name)
+                    .ok_or_else(|| error(format!("{} isn't defined", name)))?;
+                expanding.push(name.clone());
+                self.instantiate(egraph, definition, copies, species, expanding)?;
+                expanding.pop();
+            }
+            Process::Species {
+                segments,
+                joins,
+                line,
+                column,
+            } => {
+                let id: Id = add_species(egraph, segments, joins).map_err(|error: MetaError| {
+                    ParseError {
+                        line: *line,
+                        column: *column,
+                        message: error.to_string(),
+                    }
+                })?;
+                species.push((id, copies));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn add_species(
+    egraph: &mut EGraph<Language, Meta>,
+    segments: &[Segment],
+    joins: &[&'static str],
+) -> Result<Id, MetaError> {
+    // A lone strand.
+    if let [segment] = segments {
+        if segment.duplex.is_none() {
+            let strand: Vec<Domain> = if segment.upper_left.is_empty() {
+                segment.lower_left.iter().rev().cloned().collect()
+            } else {
+                segment.upper_left.clone()
+            };
+            return add_complex_to_egraph(egraph, &vec![strand], &Vec::default());
+        }
+    }
+
+    // Walk along the gate, breaking the upper strand wherever segments are
+    // joined by their lower strands, and vice versa. Each duplex is recorded
+    // by which upper and lower strand it's on, and where.
+    let mut upper_strands: Vec<Vec<Domain>> = vec![Vec::default()];
+    let mut lower_strands: Vec<Vec<Domain>> = vec![Vec::default()];
+    let mut duplexes: Vec<(usize, usize, usize, usize, Vec<Domain>)> = Vec::default();
+    for (i, segment) in segments.iter().enumerate() {
+        if i > 0 {
+            match joins[i - 1] {
+                ":" => upper_strands.push(Vec::default()),
+                _ => lower_strands.push(Vec::default()),
+            }
+        }
+        let duplex: &Vec<Domain> = segment.duplex.as_ref().unwrap();
+        let upper: &mut Vec<Domain> = upper_strands.last_mut().unwrap();
+        upper.extend(segment.upper_left.iter().cloned());
+        let upper_start: usize = upper.len();
+        upper.extend(duplex.iter().cloned());
+        upper.extend(segment.upper_right.iter().cloned());
+        let lower: &mut Vec<Domain> = lower_strands.last_mut().unwrap();
+        lower.extend(segment.lower_left.iter().cloned());
+        let lower_start: usize = lower.len();
+        lower.extend(duplex.iter().map(Domain::complement));
+        lower.extend(segment.lower_right.iter().cloned());
+        duplexes.push((
+            upper_strands.len() - 1,
+            upper_start,
+            lower_strands.len() - 1,
+            lower_start,
+            duplex.clone(),
+        ));
+    }
+
+    // Lower strands are written right to left, so they're reversed, and
+    // their duplexes counted from the other end. Every strand holding a
+    // duplex has domains, so dropping empty strands only shifts the indices.
+    let upper_index: Vec<usize> = upper_strands
+        .iter()
+        .scan(0, |count: &mut usize, strand: &Vec<Domain>| {
+            *count += !strand.is_empty() as usize;
+            Some(*count - 1)
+        })
+        .collect();
+    let upper_count: usize = upper_strands
+        .iter()
+        .filter(|strand: &&Vec<Domain>| !strand.is_empty())
+        .count();
+    let lower_index: Vec<usize> = lower_strands
+        .iter()
+        .scan(upper_count, |count: &mut usize, strand: &Vec<Domain>| {
+            *count += !strand.is_empty() as usize;
+            Some(*count - 1)
+        })
+        .collect();
+    let pairings: Vec<Pairing> = duplexes
+        .into_iter()
+        .map(
+            |(upper, upper_start, lower, lower_start, duplex): (
+                usize,
+                usize,
+                usize,
+                usize,
+                Vec<Domain>,
+            )| Pairing {
+                bottom: Location {
+                    strand: upper_index[upper],
+                    domain: upper_start,
+                },
+                top: Location {
+                    strand: lower_index[lower],
+                    domain: lower_strands[lower].len() - lower_start - duplex.len(),
+                },
+                domains: duplex,
+            },
+        )
+        .collect();
+
+    let strands: Vec<Vec<Domain>> = upper_strands
+        .into_iter()
+        .chain(
+            lower_strands
+                .into_iter()
+                .map(|lower: Vec<Domain>| lower.into_iter().rev().collect()),
+        )
+        .filter(|strand: &Vec<Domain>| !strand.is_empty())
+        .collect();
+    add_complex_to_egraph(egraph, &strands, &pairings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_gate_and_strands() {
+        let mut egraph = EGraph::<Language, Meta>::default();
+        let system: ParsedSystem = parse_dsd(
+            &mut egraph,
+            "(* A toehold-mediated strand displacement. *)
+             def Input = <t^ x>
+             new t
+             ( 10 * Input
+             | {t^*}[x]<y>
+             )",
+        )
+        .unwrap();
+
+        let t: Domain = Domain::toehold(system.domain_ids["t"].clone());
+        let x: Domain = Domain::long(system.domain_ids["x"].clone());
+        let y: Domain = Domain::long(system.domain_ids["y"].clone());
+        assert_eq!(system.domain_ids["t"], DomainId::Named(Name::new("t")));
+        assert_eq!(system.species.len(), 2);
+        assert_eq!(system.species[0].1, 10);
+        assert_eq!(
+            egraph[system.species[0].0].metadata.value,
+            Some(Value::ComplexValue {
+                strands: vec![vec![t.clone(), x.clone()]],
+                pairings: vec![],
+            })
+        );
+        assert_eq!(
+            egraph[system.species[1].0].metadata.value,
+            Some(Value::ComplexValue {
+                strands: vec![
+                    vec![x.complement(), t.complement()],
+                    vec![x.clone(), y.clone()],
+                ],
+                pairings: vec![Pairing {
+                    bottom: Location {
+                        strand: 0,
+                        domain: 0,
+                    },
+                    top: Location {
+                        strand: 1,
+                        domain: 0,
+                    },
+                    domains: vec![x.complement()],
+                }],
+            })
+        );
+        // The duplex is added as it's read from the complex's first strand.
+        assert_eq!(
+            find_double_strand_cells(&egraph, &[x.complement()]).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let mut egraph = EGraph::<Language, Meta>::default();
+        assert_eq!(
+            parse_dsd(&mut egraph, "<t^ x>\n| <x y"),
+            Err(ParseError {
+                line: 2,
+                column: 7,
+                message: "Expected a domain or '>', found end of input".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_dsd(&mut egraph, "<t^ x> | <t y>"),
+            Err(ParseError {
+                line: 1,
+                column: 11,
+                message: "Domain t is used both as a toehold and as a long domain".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_dsd(&mut egraph, "( <a> | Missing )")
+                .unwrap_err()
+                .column,
+            9
+        );
+    }
+}
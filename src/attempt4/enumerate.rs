@@ -0,0 +1,369 @@
+use super::kinetics::RateModel;
+use super::rewrites::run;
+use super::*;
+use egg::Rewrite;
+use log::debug;
+
+/// A reaction between species, as found by enumerate().
+///
+/// Species are whole complexes, identified by their ComplexValue; a free
+/// strand is a complex with just itself as a member. Strands are shared by
+/// value throughout the egraph, so a species is really a domain-level pattern,
+/// not a particular molecule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reaction {
+    /// Sorted.
+    pub reactants: Vec<Value>,
+    /// Sorted.
+    pub products: Vec<Value>,
+    /// The name of the rewrite which fired.
+    pub rule: String,
+    /// The rate constant, in /M/s for bimolecular reactions and /s for
+    /// unimolecular ones. enumerate() only fills this in when given a
+    /// kinetics::RateModel.
+    pub rate: Option<f64>,
+}
+
+/// Enumerates the reactions reachable from `strands`.
+///
+/// `structural_rules` are rewrites which don't change which species are
+/// present (e.g. strand_cell_associativity()). `reaction_rules` are rewrites
+/// like toehold_bind() or branch_migration(), which record each reaction they
+/// find in the egraph. Both are run, in turn, until the egraph stops growing.
+///
+/// If `rate_model` is given, it's used to fill in the rate of each reaction.
+///
+/// Each of `strands` is added as a strand instance whose id is its index, so
+/// that identical strands are kept apart (see rewrites::homodimer_bind()) and
+/// products can be traced back to them with strand_ids(). Each stands for a
+/// species rather than a single molecule, so it can react with another copy of
+/// itself; see ReactionRule::can_meet() in rewrites.
+///
+/// Fails if one of `strands` is ill-formed, or if a rule produces something
+/// ill-formed.
+pub fn enumerate(
+    strands: &[Vec<Domain>],
+    structural_rules: &[Rewrite<Language, Meta>],
+    reaction_rules: &[Rewrite<Language, Meta>],
+    rate_model: Option<&RateModel>,
+) -> Result<Vec<Reaction>, MetaError> {
+    let mut egraph = EGraph::<Language, Meta>::default();
+    for (i, strand) in strands.iter().enumerate() {
+        add_strand_instance_to_egraph(&mut egraph, i as StrandIdValue, strand)?;
+    }
+
+    enumerate_in(&mut egraph, structural_rules, reaction_rules, rate_model)
+}
+
+/// Like enumerate(), but starting from whatever is already in `egraph` (e.g.
+/// from dsd::parse_dsd()), which is left holding every species found, so that
+/// they can be looked up with find_species().
+pub fn enumerate_in(
+    egraph: &mut EGraph<Language, Meta>,
+    structural_rules: &[Rewrite<Language, Meta>],
+    reaction_rules: &[Rewrite<Language, Meta>],
+    rate_model: Option<&RateModel>,
+) -> Result<Vec<Reaction>, MetaError> {
+    loop {
+        let egraph_size: usize = egraph.total_size();
+        run(egraph, structural_rules)?;
+        run(egraph, reaction_rules)?;
+        if egraph.total_size() == egraph_size {
+            break;
+        }
+    }
+
+    Ok(reactions(egraph, rate_model))
+}
+
+/// The reactions recorded in `egraph` by reaction rules, in the order they
+/// were found. Reactions which don't change anything are left out, as are
+/// repeats (e.g. the same reaction between different strand instances). If
+/// `rate_model` is given, it's used to fill in the rate of each reaction.
+pub fn reactions(egraph: &EGraph<Language, Meta>, rate_model: Option<&RateModel>) -> Vec<Reaction> {
+    let mut eclasses: Vec<(Id, &Value)> = egraph
+        .classes()
+        .filter_map(|eclass| match eclass.metadata.value.as_ref() {
+            Some(value @ Value::ReactionValue { .. }) => Some((eclass.id, value)),
+            _ => None,
+        })
+        .collect();
+    eclasses.sort();
+
+    let mut reactions: Vec<Reaction> = Vec::default();
+    for (_, value) in eclasses {
+        let mut reaction: Reaction = match value {
+            Value::ReactionValue {
+                rule,
+                reactants,
+                products,
+            } => Reaction {
+                reactants: reactants.clone(),
+                products: products.clone(),
+                rule: rule.clone(),
+                rate: None,
+            },
+            _ => continue,
+        };
+        reaction.rate = rate_model.and_then(|rate_model: &RateModel| rate_model.rate(&reaction));
+        if reaction.reactants == reaction.products || reactions.contains(&reaction) {
+            continue;
+        }
+        debug!("Found reaction {:?}", reaction);
+        reactions.push(reaction);
+    }
+    reactions
+}
+
+/// Every species taking part in `reactions`, sorted and without duplicates.
+pub fn species(reactions: &[Reaction]) -> Vec<Value> {
+    let mut species: Vec<Value> = reactions
+        .iter()
+        .flat_map(|reaction: &Reaction| reaction.reactants.iter().chain(reaction.products.iter()))
+        .cloned()
+        .collect();
+    species.sort();
+    species.dedup();
+    species
+}
+
+/// Finds an eclass holding the species `value`.
+pub fn find_species(egraph: &EGraph<Language, Meta>, value: &Value) -> Option<Id> {
+    let pattern: &str = match value {
+        Value::StrandCellValue(_) => "(strand-cell ?a ?b)",
+        Value::StrandValue { .. } => "(strand ?a ?b)",
+        Value::DoubleStrandCellValue(_) => "(double-strand-cell ?a ?b ?c)",
+        Value::ComplexValue { .. } => {
+            return egraph
+                .classes()
+                .find(|eclass| {
+                    eclass.metadata.value.as_ref() == Some(value)
+                        && eclass
+                            .nodes
+                            .iter()
+                            .any(|enode| enode.op == Language::Complex)
+                })
+                .map(|eclass| eclass.id)
+        }
+        Value::DomainIdValue(_)
+        | Value::DomainValue(_)
+        | Value::StrandIdValue(_)
+        | Value::LengthValue(_)
+        | Value::LocationValue(_)
+        | Value::PairingValue(_)
+        | Value::RuleNameValue(_)
+        | Value::ReactionSideValue(_)
+        | Value::ReactionValue { .. } => return None,
+    };
+    find_eclasses_with_value(egraph, pattern, value)
+        .first()
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::rewrites::*;
+    use super::*;
+
+    /// The value of the complex of `strands` bound by `pairings`.
+    fn complex_value(strands: &[Vec<Domain>], pairings: &[Pairing]) -> Value {
+        let mut egraph = EGraph::<Language, Meta>::default();
+        let id: Id =
+            add_complex_to_egraph(&mut egraph, &strands.to_vec(), &pairings.to_vec()).unwrap();
+        egraph[id].metadata.value.clone().unwrap()
+    }
+
+    fn pairing(bottom: (usize, usize), top: (usize, usize), domains: &[Domain]) -> Pairing {
+        Pairing {
+            bottom: Location {
+                strand: bottom.0,
+                domain: bottom.1,
+            },
+            top: Location {
+                strand: top.0,
+                domain: top.1,
+            },
+            domains: domains.to_vec(),
+        }
+    }
+
+    #[test]
+    fn toehold_exchange() {
+        let t0: Domain = Domain::toehold(DomainId::DomainId(0));
+        let l1: Domain = Domain::long(DomainId::DomainId(1));
+        let mut structural_rules = Vec::default();
+        structural_rules.extend(strand_cell_associativity());
+        structural_rules.extend(strand_cell_nil_commutativity());
+        let input: Vec<Domain> = vec![t0.clone(), l1.clone()];
+        let toehold_bound = complex_value(
+            &[input.clone(), vec![t0.complement()]],
+            &[pairing((0, 0), (1, 0), std::slice::from_ref(&t0))],
+        );
+        let mut free_strands: Vec<Value> = vec![
+            complex_value(std::slice::from_ref(&input), &[]),
+            complex_value(&[vec![t0.complement()]], &[]),
+        ];
+        free_strands.sort();
+
+        // Only the toehold can bind, so it falls off again.
+        let reactions: Vec<Reaction> = enumerate(
+            &[input.clone(), vec![t0.complement()]],
+            &structural_rules,
+            &[
+                toehold_bind(),
+                bind(),
+                toehold_dissociation(DEFAULT_TOEHOLD_LENGTH),
+            ],
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            reactions,
+            vec![
+                Reaction {
+                    reactants: free_strands.clone(),
+                    products: vec![toehold_bound.clone()],
+                    rule: "toehold-bind".to_string(),
+                    rate: None,
+                },
+                Reaction {
+                    reactants: vec![toehold_bound.clone()],
+                    products: free_strands.clone(),
+                    rule: "toehold-dissociation".to_string(),
+                    rate: None,
+                },
+            ]
+        );
+
+        // With the whole complement, the toehold is followed by bind().
+        let complement: Vec<Domain> = complementary_strand(&input);
+        let reactions: Vec<Reaction> = enumerate(
+            &[input.clone(), complement.clone()],
+            &structural_rules,
+            &[
+                toehold_bind(),
+                bind(),
+                toehold_dissociation(DEFAULT_TOEHOLD_LENGTH),
+            ],
+            None,
+        )
+        .unwrap();
+        let toehold_bound = complex_value(
+            &[input.clone(), complement.clone()],
+            &[pairing((0, 0), (1, 1), std::slice::from_ref(&t0))],
+        );
+        assert!(reactions.contains(&Reaction {
+            reactants: vec![toehold_bound.clone()],
+            products: vec![complex_value(
+                &[input.clone(), complement.clone()],
+                &[pairing((0, 0), (1, 0), &input)]
+            )],
+            rule: "bind".to_string(),
+            rate: None,
+        }));
+        assert!(species(&reactions).contains(&toehold_bound));
+    }
+
+    #[test]
+    fn homodimer() {
+        let strand: Vec<Domain> = parse_domains("t0^ 1 t0^*").unwrap();
+        let mut egraph = EGraph::<Language, Meta>::default();
+        add_strand_instance_to_egraph(&mut egraph, 0, &strand).unwrap();
+        add_strand_instance_to_egraph(&mut egraph, 1, &strand).unwrap();
+        let reactions: Vec<Reaction> =
+            enumerate_in(&mut egraph, &[], &[homodimer_bind()], None).unwrap();
+
+        let dimer = Value::ComplexValue {
+            strands: vec![strand.clone(), strand.clone()],
+            pairings: vec![
+                pairing((0, 0), (1, 2), &parse_domains("t0^").unwrap()),
+                pairing((0, 2), (1, 0), &parse_domains("t0^*").unwrap()),
+            ],
+        };
+        let monomer = complex_value(std::slice::from_ref(&strand), &[]);
+        assert_eq!(
+            reactions,
+            vec![Reaction {
+                reactants: vec![monomer.clone(), monomer.clone()],
+                products: vec![dimer.clone()],
+                rule: "homodimer-bind".to_string(),
+                rate: None,
+            }]
+        );
+        let dimer_id: Id = find_species(&egraph, &dimer).unwrap();
+        assert_eq!(strand_ids(&egraph, dimer_id), vec![0, 1]);
+
+        // A single input strand is a species, not a molecule, so it binds
+        // another copy of itself just the same.
+        let mut egraph = EGraph::<Language, Meta>::default();
+        add_strand_instance_to_egraph(&mut egraph, 0, &strand).unwrap();
+        let reactions: Vec<Reaction> =
+            enumerate_in(&mut egraph, &[], &[homodimer_bind()], None).unwrap();
+        assert_eq!(
+            reactions,
+            vec![Reaction {
+                reactants: vec![monomer.clone(), monomer.clone()],
+                products: vec![dimer.clone()],
+                rule: "homodimer-bind".to_string(),
+                rate: None,
+            }]
+        );
+        let dimer_id: Id = find_species(&egraph, &dimer).unwrap();
+        // Both copies are traced back to the one input strand.
+        assert_eq!(strand_ids(&egraph, dimer_id), vec![0]);
+    }
+
+    #[test]
+    fn two_copies() {
+        let strand: Vec<Domain> = parse_domains("t0^ 1 t0^*").unwrap();
+        let reactions: Vec<Reaction> =
+            enumerate(std::slice::from_ref(&strand), &[], &[toehold_bind()], None).unwrap();
+
+        // Two copies of the strand bind by a toehold, but no complex can hold
+        // a third.
+        let monomer = complex_value(std::slice::from_ref(&strand), &[]);
+        assert_eq!(
+            reactions,
+            vec![Reaction {
+                reactants: vec![monomer.clone(), monomer.clone()],
+                products: vec![complex_value(
+                    &[strand.clone(), strand.clone()],
+                    &[pairing((0, 0), (1, 2), &parse_domains("t0^").unwrap())]
+                )],
+                rule: "toehold-bind".to_string(),
+                rate: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn strand_instances() {
+        let mut egraph = EGraph::<Language, Meta>::default();
+        add_strand_instance_to_egraph(&mut egraph, 0, &parse_domains("t0^ 1").unwrap()).unwrap();
+        add_strand_instance_to_egraph(&mut egraph, 1, &parse_domains("1* t0^*").unwrap()).unwrap();
+        let reactions: Vec<Reaction> = enumerate_in(
+            &mut egraph,
+            &[],
+            &[
+                toehold_bind(),
+                bind(),
+                toehold_dissociation(DEFAULT_TOEHOLD_LENGTH),
+            ],
+            None,
+        )
+        .unwrap();
+
+        // Every species, bound or not, is traced back to its strands.
+        let found: Vec<Value> = species(&reactions);
+        assert_eq!(found.len(), 4);
+        for value in found {
+            let strand_count: usize = match &value {
+                Value::ComplexValue { strands, .. } => strands.len(),
+                _ => panic!("{:?} isn't a complex", value),
+            };
+            let ids: Vec<StrandIdValue> =
+                strand_ids(&egraph, find_species(&egraph, &value).unwrap());
+            assert_eq!(ids.len(), strand_count);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Extracting one canonical form for a strand or duplex.
+//!
+//! Once strand_cell_associativity() and strand_cell_nil_commutativity() (and
+//! their double strand counterparts) have run, an eclass holds every way of
+//! nesting the same strand. The cost functions here prefer one nesting, so
+//! that extraction gives the same RecExpr for the same strand however it was
+//! built:
+//!
+//! ```text
+//! right-nested: (strand-cell d0 (strand-cell d1 (strand-cell d2 nil)))
+//! left-nested:  (strand-cell (strand-cell (strand-cell nil d0) d1) d2)
+//! ```
+//!
+//! and likewise `(double-strand-cell <bottom> <top> <rest>)` or
+//! `(double-strand-cell <rest> <bottom> <top>)` for duplexes.
+
+use super::*;
+use egg::{CostFunction, Extractor, RecExpr};
+
+/// Which way canonical forms nest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nesting {
+    /// Each cell holds its first domain (or double strand cell), followed by
+    /// the rest.
+    Right,
+    /// Each cell holds everything but its last domain (or double strand cell),
+    /// followed by that.
+    Left,
+}
+
+/// What kind of thing an eclass holds, as far as nesting is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Nil,
+    Domain,
+    StrandCell,
+    DoubleStrandCell,
+    Other,
+}
+
+/// The cost of an expression under CanonicalForm: first how many of its
+/// strand cells and double strand cells are nested the wrong way, then its
+/// size.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct CanonicalCost {
+    pub misnested: usize,
+    pub size: usize,
+    kind: Kind,
+}
+
+/// A cost function under which the cheapest form of a strand or duplex is the
+/// one nested as `nesting` says.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CanonicalForm {
+    pub nesting: Nesting,
+}
+
+impl CanonicalForm {
+    pub fn right_nested() -> Self {
+        CanonicalForm {
+            nesting: Nesting::Right,
+        }
+    }
+
+    pub fn left_nested() -> Self {
+        CanonicalForm {
+            nesting: Nesting::Left,
+        }
+    }
+}
+
+impl CostFunction<Language> for CanonicalForm {
+    type Cost = CanonicalCost;
+
+    fn cost(&mut self, enode: &ENode<Language, CanonicalCost>) -> CanonicalCost {
+        let kinds: Vec<Kind> = enode
+            .children
+            .iter()
+            .map(|child: &CanonicalCost| child.kind)
+            .collect();
+        let is_rest = |kind: Kind, of: Kind| kind == Kind::Nil || kind == of;
+
+        let (kind, well_nested): (Kind, bool) = match enode.op {
+            Language::Nil => (Kind::Nil, true),
+            Language::Domain => (Kind::Domain, true),
+            Language::StrandCell => (
+                Kind::StrandCell,
+                match (self.nesting, kinds.as_slice()) {
+                    (Nesting::Right, [Kind::Domain, rest]) => is_rest(*rest, Kind::StrandCell),
+                    (Nesting::Left, [rest, Kind::Domain]) => is_rest(*rest, Kind::StrandCell),
+                    _ => false,
+                },
+            ),
+            Language::DoubleStrandCell => (
+                Kind::DoubleStrandCell,
+                match (self.nesting, kinds.as_slice()) {
+                    (Nesting::Right, [Kind::StrandCell, Kind::StrandCell, rest]) => {
+                        is_rest(*rest, Kind::DoubleStrandCell)
+                    }
+                    (Nesting::Left, [rest, Kind::StrandCell, Kind::StrandCell]) => {
+                        is_rest(*rest, Kind::DoubleStrandCell)
+                    }
+                    _ => false,
+                },
+            ),
+            _ => (Kind::Other, true),
+        };
+
+        CanonicalCost {
+            misnested: enode
+                .children
+                .iter()
+                .map(|child: &CanonicalCost| child.misnested)
+                .sum::<usize>()
+                + if well_nested { 0 } else { 1 },
+            size: 1 + enode
+                .children
+                .iter()
+                .map(|child: &CanonicalCost| child.size)
+                .sum::<usize>(),
+            kind: kind,
+        }
+    }
+}
+
+/// The canonical form of the strand or duplex in `eclass`, nested as
+/// `nesting` says. Returns None if the eclass doesn't hold a strand cell or
+/// double strand cell, or if no form of it is nested all the way through,
+/// which usually means the associativity and nil commutativity rewrites
+/// haven't been run to saturation.
+pub fn canonical_form(
+    egraph: &EGraph<Language, Meta>,
+    eclass: Id,
+    nesting: Nesting,
+) -> Option<RecExpr<Language>> {
+    match egraph[eclass].metadata.value.as_ref() {
+        Some(Value::StrandCellValue(_)) | Some(Value::DoubleStrandCellValue(_)) => (),
+        _ => return None,
+    }
+
+    let (cost, expr) = Extractor::new(egraph, CanonicalForm { nesting: nesting }).find_best(eclass);
+    if cost.misnested == 0 {
+        Some(expr)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::rewrites::*;
+    use super::*;
+
+    #[test]
+    fn canonical_strands() {
+        let domains: Vec<Domain> = parse_domains("t0^ 1 2").unwrap();
+        let mut egraph = EGraph::<Language, Meta>::default();
+        let id: Id = add_strand_to_egraph(&mut egraph, &domains).unwrap();
+        let left_nested: RecExpr<Language> = "(strand-cell
+                                               (strand-cell
+                                                (strand-cell
+                                                 nil
+                                                 (domain (toehold-domain (domain-id 0))))
+                                                (domain (long-domain (domain-id 1))))
+                                               (domain (long-domain (domain-id 2))))"
+            .parse()
+            .unwrap();
+        let right_nested: RecExpr<Language> = "(strand-cell
+                                                (domain (toehold-domain (domain-id 0)))
+                                                (strand-cell
+                                                 (domain (long-domain (domain-id 1)))
+                                                 (strand-cell
+                                                  (domain (long-domain (domain-id 2)))
+                                                  nil)))"
+            .parse()
+            .unwrap();
+
+        // Only the shape it was added in is there to begin with.
+        assert_eq!(
+            canonical_form(&egraph, id, Nesting::Left),
+            Some(left_nested.clone())
+        );
+        assert_eq!(canonical_form(&egraph, id, Nesting::Right), None);
+
+        let mut rules = strand_cell_associativity();
+        rules.extend(strand_cell_nil_commutativity());
+        run(&mut egraph, &rules).unwrap();
+        let id: Id = egraph.find(id);
+        assert_eq!(
+            canonical_form(&egraph, id, Nesting::Right),
+            Some(right_nested)
+        );
+        assert_eq!(
+            canonical_form(&egraph, id, Nesting::Left),
+            Some(left_nested)
+        );
+
+        // Duplexes nest the same way.
+        let duplex: Id =
+            add_double_strand_to_egraph(&mut egraph, &parse_domains("t0^").unwrap()).unwrap();
+        assert_eq!(
+            canonical_form(&egraph, duplex, Nesting::Right)
+                .unwrap()
+                .to_string(),
+            "(double-strand-cell (strand-cell (domain (toehold-domain (domain-id 0))) nil) \
+             (strand-cell (domain (toehold-domain (complement (domain-id 0)))) nil) nil)"
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use super::enumerate::Reaction;
+use super::rewrites::ToeholdLayout;
+use super::*;
+
+/// Assigns rate constants to reactions, from the rule which fired and the
+/// nucleotide lengths of the domains involved (see Domain::length()), roughly
+/// following Zhang &
+/// Winfree, "Control of DNA strand displacement kinetics using toehold
+/// exchange" (2009).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateModel {
+    /// Rate at which complementary toeholds find each other, in /M/s.
+    pub bimolecular_binding_rate: f64,
+    /// Rate at which an already-bound double strand zips up, in /s.
+    pub zipping_rate: f64,
+    /// Free energy gained per base pair formed, in kcal/mol (positive).
+    pub base_pair_free_energy: f64,
+    /// RT at the temperature we're simulating, in kcal/mol.
+    pub rt: f64,
+    /// Rate of a single step of branch migration, in /s.
+    pub branch_migration_step_rate: f64,
+    /// How much slower a step of four-way branch migration is than a step of
+    /// three-way branch migration.
+    pub four_way_slowdown: f64,
+    /// Rate at which an incumbent falls off once nothing holds it, in /s.
+    pub release_rate: f64,
+    /// Rate at which a hairpin loop of a single nucleotide would close, in /s.
+    pub hairpin_closing_rate: f64,
+}
+
+impl Default for RateModel {
+    fn default() -> Self {
+        RateModel {
+            bimolecular_binding_rate: 3e6,
+            zipping_rate: 1e6,
+            base_pair_free_energy: 1.7,
+            // 25C.
+            rt: 0.593,
+            // About 1us per nucleotide step.
+            branch_migration_step_rate: 1e6,
+            four_way_slowdown: 100.0,
+            release_rate: 1e6,
+            hairpin_closing_rate: 1e6,
+        }
+    }
+}
+
+impl RateModel {
+    /// The rate constant of `reaction`, or None if we don't know how to rate
+    /// the rule which produced it.
+    pub fn rate(&self, reaction: &Reaction) -> Option<f64> {
+        match reaction.rule.as_str() {
+            "bind" => Some(self.zipping_rate),
+            // Both contacts form together, as fast as a single toehold binds.
+            "homodimer-bind" => Some(self.bimolecular_binding_rate),
+            "toehold-dissociation" => Some(self.dissociation_rate(
+                bound_length(&reaction.reactants).saturating_sub(bound_length(&reaction.products)),
+            )),
+            "branch-migration" => Some(self.branch_migration_rate(self.migrated_length(reaction))),
+            "four-way-branch-migration" => Some(
+                self.branch_migration_rate(self.exchanged_length(reaction))
+                    / self.four_way_slowdown,
+            ),
+            "strand-release" => Some(self.release_rate),
+            "hairpin-formation" => {
+                let (strands, product_pairings): (&Vec<Vec<Domain>>, &Vec<Pairing>) =
+                    match reaction.products.as_slice() {
+                        [Value::ComplexValue { strands, pairings }] => (strands, pairings),
+                        _ => return None,
+                    };
+                // The stem is the pairing the strand folded back onto itself
+                // to make.
+                let reactant_pairings: Vec<&Pairing> = pairings(&reaction.reactants);
+                let stem: &Pairing = product_pairings.iter().find(|pairing: &&Pairing| {
+                    pairing.bottom.strand == pairing.top.strand
+                        && !reactant_pairings.contains(pairing)
+                })?;
+                let loop_length: usize = total_length(
+                    strands
+                        .get(stem.bottom.strand)?
+                        .get(stem.bottom.domain + stem.domains.len()..stem.top.domain)?,
+                );
+                // Closing a loop costs entropy; see Jacobson & Stockmayer.
+                Some(self.hairpin_closing_rate * (loop_length as f64).powf(-1.5))
+            }
+            rule => ToeholdLayout::from_rule_name(rule)
+                .map(|layout: ToeholdLayout| self.bimolecular_binding_rate * layout.penalty()),
+        }
+    }
+
+    /// Fills in the rate of each of `reactions`.
+    pub fn assign_rates(&self, reactions: &mut [Reaction]) {
+        for reaction in reactions.iter_mut() {
+            reaction.rate = self.rate(reaction);
+        }
+    }
+
+    /// The rate at which `bound_length` base pairs fall apart, from detailed
+    /// balance with binding.
+    pub fn dissociation_rate(&self, bound_length: usize) -> f64 {
+        self.bimolecular_binding_rate
+            * (-(bound_length as f64) * self.base_pair_free_energy / self.rt).exp()
+    }
+
+    /// Branch migration across `migrated_length` nucleotides is a random walk,
+    /// so it takes a number of steps quadratic in the length.
+    pub fn branch_migration_rate(&self, migrated_length: usize) -> f64 {
+        self.branch_migration_step_rate / (std::cmp::max(migrated_length, 1) as f64).powi(2)
+    }
+
+    /// How many nucleotides the invader took over: how much longer one of its
+    /// pairings is in the products than in the reactants.
+    fn migrated_length(&self, reaction: &Reaction) -> usize {
+        let reactant_pairings: Vec<&Pairing> = pairings(&reaction.reactants);
+        pairings(&reaction.products)
+            .iter()
+            .filter_map(|product: &&Pairing| {
+                reactant_pairings
+                    .iter()
+                    .filter(|reactant: &&&Pairing| {
+                        reactant.domains.len() < product.domains.len()
+                            && (product.domains.starts_with(&reactant.domains)
+                                || product.domains.ends_with(&reactant.domains))
+                    })
+                    .map(|reactant: &&Pairing| {
+                        total_length(&product.domains) - total_length(&reactant.domains)
+                    })
+                    .min()
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// How many nucleotides swapped partners in a four-way branch migration.
+    /// Every base pair is either exchanged or kept, and an exchanged one is
+    /// counted once for the two double strands it came from, so this is how
+    /// many base pairs there are fewer of in the products.
+    fn exchanged_length(&self, reaction: &Reaction) -> usize {
+        bound_length(&reaction.reactants).saturating_sub(bound_length(&reaction.products))
+    }
+}
+
+/// Every pairing of the complexes in `species`.
+fn pairings(species: &[Value]) -> Vec<&Pairing> {
+    species
+        .iter()
+        .flat_map(|value: &Value| match value {
+            Value::ComplexValue { pairings, .. } => pairings.iter().collect(),
+            _ => vec![],
+        })
+        .collect()
+}
+
+/// How many base pairs hold `species` together, in total.
+fn bound_length(species: &[Value]) -> usize {
+    pairings(species)
+        .iter()
+        .map(|pairing: &&Pairing| total_length(&pairing.domains))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pairing as (bottom, top, domains).
+    type PairingSpec = ((usize, usize), (usize, usize), Vec<Domain>);
+
+    /// The value of the complex of `strands` bound by `pairings`.
+    fn complex(strands: &[Vec<Domain>], pairings: &[PairingSpec]) -> Value {
+        let mut egraph = EGraph::<Language, Meta>::default();
+        let pairings: Vec<Pairing> = pairings
+            .iter()
+            .map(|(bottom, top, domains)| Pairing {
+                bottom: Location {
+                    strand: bottom.0,
+                    domain: bottom.1,
+                },
+                top: Location {
+                    strand: top.0,
+                    domain: top.1,
+                },
+                domains: domains.clone(),
+            })
+            .collect();
+        let id: Id = add_complex_to_egraph(&mut egraph, &strands.to_vec(), &pairings).unwrap();
+        egraph[id].metadata.value.clone().unwrap()
+    }
+
+    #[test]
+    fn rates() {
+        let rate_model = RateModel::default();
+        let t0: Domain = parse_domains("t0:5^").unwrap()[0].clone();
+        let t1: Domain = Domain::toehold(DomainId::DomainId(1));
+        let l2: Domain = Domain::long(DomainId::DomainId(2));
+        assert_eq!(t0.complement().length(), 5);
+        assert_eq!(t1.length(), 6);
+        assert_eq!(l2.length(), 20);
+
+        let dissociation = |toehold: &Domain| Reaction {
+            reactants: vec![complex(
+                &[vec![toehold.clone()], vec![toehold.complement()]],
+                &[((0, 0), (1, 0), vec![toehold.clone()])],
+            )],
+            products: vec![
+                complex(&[vec![toehold.complement()]], &[]),
+                complex(&[vec![toehold.clone()]], &[]),
+            ],
+            rule: "toehold-dissociation".to_string(),
+            rate: None,
+        };
+        // Longer toeholds hold on for longer.
+        assert!(
+            rate_model.rate(&dissociation(&t1)).unwrap()
+                < rate_model.rate(&dissociation(&t0)).unwrap()
+        );
+
+        // Two copies of a strand binding each other are rated like any other
+        // binding.
+        let strand: Vec<Domain> = vec![t1.clone(), l2.clone(), t1.complement()];
+        let homodimer = Reaction {
+            reactants: vec![
+                complex(std::slice::from_ref(&strand), &[]),
+                complex(std::slice::from_ref(&strand), &[]),
+            ],
+            products: vec![complex(
+                &[strand.clone(), strand.clone()],
+                &[
+                    ((0, 0), (1, 2), vec![t1.clone()]),
+                    ((0, 2), (1, 0), vec![t1.complement()]),
+                ],
+            )],
+            rule: "homodimer-bind".to_string(),
+            rate: None,
+        };
+        assert_eq!(
+            rate_model.rate(&homodimer),
+            Some(rate_model.bimolecular_binding_rate)
+        );
+
+        // An invader bound by t0 takes over l2 from an incumbent.
+        let l3: Domain = Domain::long(DomainId::DomainId(3));
+        let template: Vec<Domain> = vec![t0.clone(), l2.clone(), l3.clone()];
+        let invader: Vec<Domain> = complementary_strand(&template);
+        let incumbent: Vec<Domain> = complementary_strand(&[l2.clone(), l3.clone()]);
+        let strands: Vec<Vec<Domain>> = vec![template.clone(), invader.clone(), incumbent.clone()];
+        let mut reactions: Vec<Reaction> = vec![
+            Reaction {
+                reactants: vec![
+                    complex(&[vec![t0.complement()]], &[]),
+                    complex(&[vec![t0.clone()]], &[]),
+                ],
+                products: vec![complex(
+                    &[vec![t0.clone()], vec![t0.complement()]],
+                    &[((0, 0), (1, 0), vec![t0.clone()])],
+                )],
+                rule: "remote-toehold-bind-1".to_string(),
+                rate: None,
+            },
+            Reaction {
+                reactants: vec![complex(
+                    &strands,
+                    &[
+                        ((0, 0), (1, 2), vec![t0.clone()]),
+                        ((0, 1), (2, 0), vec![l2.clone(), l3.clone()]),
+                    ],
+                )],
+                products: vec![complex(
+                    &strands,
+                    &[
+                        ((0, 0), (1, 1), vec![t0.clone(), l2.clone()]),
+                        ((0, 2), (2, 0), vec![l3.clone()]),
+                    ],
+                )],
+                rule: "branch-migration".to_string(),
+                rate: None,
+            },
+            Reaction {
+                reactants: vec![],
+                products: vec![],
+                rule: "strand-cell-associativity-0".to_string(),
+                rate: None,
+            },
+        ];
+        rate_model.assign_rates(&mut reactions);
+        assert_eq!(
+            reactions[0].rate,
+            Some(rate_model.bimolecular_binding_rate * ToeholdLayout::Remote(1).penalty())
+        );
+        assert_eq!(reactions[1].rate, Some(1e6 / 400.0));
+        assert_eq!(reactions[2].rate, None);
+
+        // Reactions which don't have the shape their rule produces can't be
+        // rated.
+        let strand: Vec<Domain> = vec![t0.clone(), l2.clone(), t0.complement()];
+        let hairpin = |stem_top: usize| Reaction {
+            reactants: vec![complex(std::slice::from_ref(&strand), &[])],
+            products: vec![complex(
+                std::slice::from_ref(&strand),
+                &[((0, 0), (0, stem_top), vec![t0.clone()])],
+            )],
+            rule: "hairpin-formation".to_string(),
+            rate: None,
+        };
+        assert_eq!(
+            rate_model.rate(&hairpin(2)),
+            Some(rate_model.hairpin_closing_rate * 20f64.powf(-1.5))
+        );
+        let mut malformed: Reaction = hairpin(2);
+        malformed.products.clear();
+        assert_eq!(rate_model.rate(&malformed), None);
+    }
+}
@@ -1,19 +1,447 @@
+use self::symbol::{Name, Symbol};
 use egg::{define_language, EGraph, ENode, Id, Metadata};
 
+pub mod crn;
+pub mod crosstalk;
+pub mod design;
+pub mod dsd;
+pub mod enumerate;
+pub mod extract;
+pub mod kinetics;
+pub mod pil;
 pub mod rewrites;
+pub mod sbml;
+pub mod sequence;
+pub mod simulate;
+pub mod structure;
+pub mod symbol;
 
 type DomainIdValue = u32;
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum DomainId {
     Complement(Box<DomainId>),
     DomainId(DomainIdValue),
+    /// A named domain; names and numbers never name the same domain. See
+    /// symbol.rs.
+    Named(Name),
 }
-//type StrandId = u32;
+type StrandIdValue = u32;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// A strand instance's identity as it's written in Language: `#3` for strand
+/// 3. The `#` keeps it from being read as a domain id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StrandTag(pub StrandIdValue);
+
+impl std::str::FromStr for StrandTag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with('#') {
+            s[1..]
+                .parse()
+                .map(StrandTag)
+                .map_err(|_| format!("Couldn't parse strand id '{}'", s))
+        } else {
+            Err(format!("Couldn't parse strand id '{}'", s))
+        }
+    }
+}
+
+impl std::fmt::Display for StrandTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// The name of the rule which found a reaction, as it's written in Language:
+/// `@toehold-bind`. The `@` keeps it from being read as a domain id.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RuleName(pub String);
+
+impl std::str::FromStr for RuleName {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() > 1 && s.starts_with('@') {
+            Ok(RuleName(s[1..].to_string()))
+        } else {
+            Err(format!("Couldn't parse rule name '{}'", s))
+        }
+    }
+}
+
+impl std::fmt::Display for RuleName {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "@{}", self.0)
+    }
+}
+
+/// A domain: its id and its length in nucleotides. A domain and its
+/// complement have the same length.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Domain {
-    Toehold(DomainId),
-    Long(DomainId),
+    Toehold(DomainId, usize),
+    Long(DomainId, usize),
+}
+
+/// The length of toeholds which aren't given one.
+pub const DEFAULT_TOEHOLD_LENGTH: usize = 6;
+/// The length of long domains which aren't given one.
+pub const DEFAULT_LONG_LENGTH: usize = 20;
+
+/// A domain's length as it's written in Language: `7nt` for 7 nucleotides.
+/// The suffix keeps it from being read as a domain id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NucleotideLength(pub usize);
+
+impl std::str::FromStr for NucleotideLength {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.ends_with("nt") {
+            s[..s.len() - 2]
+                .parse()
+                .map(NucleotideLength)
+                .map_err(|_| format!("Couldn't parse length '{}'", s))
+        } else {
+            Err(format!("Couldn't parse length '{}'", s))
+        }
+    }
+}
+
+impl std::fmt::Display for NucleotideLength {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}nt", self.0)
+    }
+}
+
+/// A position within a complex: domain `domain` (counting in bottom strand
+/// direction) of member strand `strand`, both from 0. Written `1:0` in
+/// Language for the first domain of the second member strand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Location {
+    pub strand: usize,
+    pub domain: usize,
+}
+
+impl std::str::FromStr for Location {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("Couldn't parse location '{}'", s);
+        let i: usize = s.find(':').ok_or_else(error)?;
+        Ok(Location {
+            strand: s[..i].parse().map_err(|_| error())?,
+            domain: s[i + 1..].parse().map_err(|_| error())?,
+        })
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}", self.strand, self.domain)
+    }
+}
+
+/// A run of bound domains within a complex. `domains`, in bottom strand
+/// direction, start at `bottom`; their complements (complementary_strand() of
+/// them) start at `top`, so the last of `domains` is bound to the domain at
+/// `top`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Pairing {
+    pub bottom: Location,
+    pub top: Location,
+    pub domains: Vec<Domain>,
+}
+
+impl Pairing {
+    /// The same pairing, read from its other side.
+    pub fn flipped(&self) -> Pairing {
+        Pairing {
+            bottom: self.top,
+            top: self.bottom,
+            domains: complementary_strand(&self.domains),
+        }
+    }
+
+    /// Each pair of bound domains in the pairing, as (bottom side, top side)
+    /// locations, in bottom strand direction.
+    pub fn domain_pairs(&self) -> Vec<(Location, Location)> {
+        (0..self.domains.len())
+            .map(|i: usize| {
+                (
+                    Location {
+                        strand: self.bottom.strand,
+                        domain: self.bottom.domain + i,
+                    },
+                    Location {
+                        strand: self.top.strand,
+                        domain: self.top.domain + self.domains.len() - 1 - i,
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+/// Groups `pairs` of bound domains on `strands` into pairings, one per helix:
+/// each pairing is as long as it can be, and is read from its side nearer the
+/// start of the complex. The inverse of Pairing::domain_pairs().
+pub fn pairings_from_pairs(
+    strands: &[Vec<Domain>],
+    pairs: &[(Location, Location)],
+) -> Vec<Pairing> {
+    let mut sorted_pairs: Vec<(Location, Location)> = pairs
+        .iter()
+        .map(|(a, b): &(Location, Location)| (std::cmp::min(*a, *b), std::cmp::max(*a, *b)))
+        .collect();
+    sorted_pairs.sort();
+    sorted_pairs.dedup();
+
+    let mut pairings: Vec<Pairing> = Vec::default();
+    let mut previous: Option<(Location, Location)> = None;
+    for (bottom, top) in sorted_pairs {
+        let continues_helix: bool = match previous {
+            Some((previous_bottom, previous_top)) => {
+                previous_bottom.strand == bottom.strand
+                    && previous_bottom.domain + 1 == bottom.domain
+                    && previous_top.strand == top.strand
+                    && previous_top.domain == top.domain + 1
+            }
+            None => false,
+        };
+        if !continues_helix {
+            pairings.push(Pairing {
+                bottom: bottom,
+                top: top,
+                domains: Vec::default(),
+            });
+        }
+        if let Some(pairing) = pairings.last_mut() {
+            // The top side of a helix runs backwards, so it starts at its
+            // latest pair.
+            pairing.top = top;
+            pairing
+                .domains
+                .extend(strands[bottom.strand].get(bottom.domain).cloned());
+        }
+        previous = Some((bottom, top));
+    }
+    pairings
+}
+
+impl DomainId {
+    /// The id of the complementary domain. Complementing a complement gives
+    /// back the original id, rather than nesting complements.
+    pub fn complement(&self) -> DomainId {
+        match self {
+            DomainId::Complement(domain_id) => (**domain_id).clone(),
+            DomainId::DomainId(_) | DomainId::Named(_) => {
+                DomainId::Complement(Box::new(self.clone()))
+            }
+        }
+    }
+
+    /// The id of the underlying domain, without any complements.
+    pub fn base(&self) -> DomainId {
+        match self {
+            DomainId::Complement(domain_id) => domain_id.base(),
+            DomainId::DomainId(_) | DomainId::Named(_) => self.clone(),
+        }
+    }
+
+    /// The underlying domain's number or name, as it's written in Language.
+    pub fn symbol(&self) -> Symbol {
+        match self {
+            DomainId::Complement(domain_id) => domain_id.symbol(),
+            DomainId::DomainId(v) => Symbol::Number(*v),
+            DomainId::Named(name) => Symbol::Name(*name),
+        }
+    }
+
+    /// Whether this is (an odd number of times) the complement of its base.
+    pub fn is_complement(&self) -> bool {
+        match self {
+            DomainId::Complement(domain_id) => !domain_id.is_complement(),
+            DomainId::DomainId(_) | DomainId::Named(_) => false,
+        }
+    }
+}
+
+impl Domain {
+    /// A toehold of the default length.
+    pub fn toehold(domain_id: DomainId) -> Domain {
+        Domain::Toehold(domain_id, DEFAULT_TOEHOLD_LENGTH)
+    }
+
+    /// A long domain of the default length.
+    pub fn long(domain_id: DomainId) -> Domain {
+        Domain::Long(domain_id, DEFAULT_LONG_LENGTH)
+    }
+
+    pub fn id(&self) -> &DomainId {
+        match self {
+            Domain::Toehold(domain_id, _) | Domain::Long(domain_id, _) => domain_id,
+        }
+    }
+
+    /// The domain's length, in nucleotides.
+    pub fn length(&self) -> usize {
+        match self {
+            Domain::Toehold(_, length) | Domain::Long(_, length) => *length,
+        }
+    }
+
+    /// This domain, but `length` nucleotides long.
+    pub fn with_length(&self, length: usize) -> Domain {
+        match self {
+            Domain::Toehold(domain_id, _) => Domain::Toehold(domain_id.clone(), length),
+            Domain::Long(domain_id, _) => Domain::Long(domain_id.clone(), length),
+        }
+    }
+
+    /// Whether this domain's length is the default for its kind, and so
+    /// needn't be written out.
+    fn has_default_length(&self) -> bool {
+        match self {
+            Domain::Toehold(_, length) => *length == DEFAULT_TOEHOLD_LENGTH,
+            Domain::Long(_, length) => *length == DEFAULT_LONG_LENGTH,
+        }
+    }
+
+    pub fn complement(&self) -> Domain {
+        match self {
+            Domain::Toehold(v, length) => Domain::Toehold(v.complement(), *length),
+            Domain::Long(v, length) => Domain::Long(v.complement(), *length),
+        }
+    }
+
+    /// The name of the underlying domain, ignoring complements, for printers
+    /// which mark complements their own way: `t<id>` for numbered toeholds,
+    /// `d<id>` for numbered long domains, and the (escaped) name for named
+    /// domains, so that no two domains share a name.
+    pub fn name(&self) -> String {
+        let (domain_id, prefix): (&DomainId, &str) = match self {
+            Domain::Toehold(domain_id, _) => (domain_id, "t"),
+            Domain::Long(domain_id, _) => (domain_id, "d"),
+        };
+        match domain_id.symbol() {
+            Symbol::Number(v) => format!("{}{}", prefix, v),
+            Symbol::Name(name) => symbol::escape_name(name.as_str()),
+        }
+    }
+}
+
+/// The total length of `domains`, in nucleotides.
+pub fn total_length(domains: &[Domain]) -> usize {
+    domains.iter().map(Domain::length).sum()
+}
+
+/// Domains print in the compact notation parse_domains() reads: toeholds are
+/// marked with a caret (and a leading `t`, if they're numbered), complements
+/// with a star, and lengths other than the default after a colon, e.g.
+/// `t0^*`, `1`, `t2:7^` or `rep_out:15*`. Names are escaped as in symbol.rs.
+impl std::fmt::Display for Domain {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let (domain_id, prefix, caret): (&DomainId, &str, &str) = match self {
+            Domain::Toehold(domain_id, _) => (
+                domain_id,
+                match domain_id.symbol() {
+                    Symbol::Number(_) => "t",
+                    Symbol::Name(_) => "",
+                },
+                "^",
+            ),
+            Domain::Long(domain_id, _) => (domain_id, "", ""),
+        };
+        write!(f, "{}{}", prefix, domain_id.symbol())?;
+        if !self.has_default_length() {
+            write!(f, ":{}", self.length())?;
+        }
+        write!(
+            f,
+            "{}{}",
+            caret,
+            if domain_id.is_complement() { "*" } else { "" }
+        )
+    }
+}
+
+impl std::fmt::Display for DomainId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DomainId::Complement(domain_id) => write!(f, "{}*", domain_id),
+            DomainId::DomainId(_) | DomainId::Named(_) => write!(f, "{}", self.symbol()),
+        }
+    }
+}
+
+/// Parses a single domain in compact notation: a domain id or name, then
+/// optionally `:` and its length, followed by `^` if it's a toehold and `*`
+/// if it's a complement. Numbered domains may also be written with a leading
+/// `t` or `d`, as in `t0^`; see symbol::Symbol. Domains without a length get
+/// the default for their kind.
+impl std::str::FromStr for Domain {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rest, is_complement): (&str, bool) = if s.ends_with('*') {
+            (&s[..s.len() - 1], true)
+        } else {
+            (s, false)
+        };
+        let (rest, is_toehold): (&str, bool) = if rest.ends_with('^') {
+            (&rest[..rest.len() - 1], true)
+        } else {
+            (rest, false)
+        };
+        let (rest, length): (&str, Option<usize>) = match rest.find(':') {
+            Some(i) => (
+                &rest[..i],
+                Some(
+                    rest[i + 1..]
+                        .parse()
+                        .map_err(|_| format!("Couldn't parse domain '{}'", s))?,
+                ),
+            ),
+            None => (rest, None),
+        };
+        let domain_id: DomainId = rest
+            .parse::<Symbol>()
+            .map_err(|_| format!("Couldn't parse domain '{}'", s))?
+            .into();
+        let domain_id: DomainId = if is_complement {
+            domain_id.complement()
+        } else {
+            domain_id
+        };
+        Ok(if is_toehold {
+            Domain::Toehold(domain_id, length.unwrap_or(DEFAULT_TOEHOLD_LENGTH))
+        } else {
+            Domain::Long(domain_id, length.unwrap_or(DEFAULT_LONG_LENGTH))
+        })
+    }
+}
+
+/// Parses whitespace-separated domains in compact notation, e.g.
+/// `"t0^ 1 2* 3"`; see Domain::from_str().
+pub fn parse_domains(notation: &str) -> Result<Vec<Domain>, String> {
+    notation.split_whitespace().map(str::parse).collect()
+}
+
+/// The inverse of parse_domains().
+pub fn print_domains(domains: &[Domain]) -> String {
+    domains
+        .iter()
+        .map(Domain::to_string)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// The domains of the strand which would bind to `domains`, in bottom strand
+/// direction: i.e. the reverse complement.
+pub fn complementary_strand(domains: &[Domain]) -> Vec<Domain> {
+    domains.iter().rev().map(Domain::complement).collect()
 }
 
 define_language! {
@@ -36,14 +464,28 @@ define_language! {
         // why, but I feel like it'd be useful.
         DoubleStrandCell = "double-strand-cell",
 
+        // A whole complex (i.e. a species): its member strands (or strand
+        // instances), followed by the pairings binding them together.
+        // complex: (complex [<strand-cell> | <strand>]... <pairing>...)
+        Complex = "complex",
+
+        // Domains bound within a complex, and where each side of them starts,
+        // counting member strands in the order the complex lists them (see
+        // Location and Pairing). A hairpin's stem is a pairing between two
+        // locations on the same strand.
+        // pairing: (pairing <bottom-Location> <top-Location> <double-strand-cell>)
+        Pairing = "pairing",
+
         // Note that a strand cell has zero or one domains; no more.
         // strand-cell: [| (strand-cell [ <strand-cell> | nil ] <domain>)
         //               | (strand-cell <domain> [ <strand-cell> | nil ])
         //               | (strand-cell [ <strand-cell> | nil ] [ <strand-cell> | nil ]) ]
         StrandCell = "strand-cell",
 
-        // domain: [ (domain (long-domain <domain-id>))
-        //          | (domain (toehold-domain <domain-id>)) ]
+        // A domain's length is given by a NucleotideLength, e.g. `7nt`; a
+        // domain without one has the default length for its kind.
+        // domain: [ (domain (long-domain <domain-id> [<NucleotideLength>]))
+        //          | (domain (toehold-domain <domain-id> [<NucleotideLength>])) ]
         Domain = "domain",
         LongDomain = "long-domain",
         ToeholdDomain = "toehold-domain",
@@ -55,13 +497,33 @@ define_language! {
 
         Nil = "nil",
 
-        // TODO(gus) give an alias for u32 here?
-        DomainIdValue(u32),
+        // A particular copy of a strand. Strand cells are shared by value, so
+        // identical strands share them; wrapping a strand cell in a strand
+        // node with an id of its own keeps copies apart.
+        // strand: (strand <StrandIdValue> <strand-cell>)
+        Strand = "strand",
+
+        // A reaction found by one of the reaction rules in rewrites: the
+        // rule's name, and the complexes it turns into which. Reactions are
+        // never unioned with anything; they're read back out with
+        // enumerate::reactions().
+        // reaction: (reaction <RuleName> (reactants <complex>...)
+        //                                (products <complex>...))
+        Reaction = "reaction",
+        Reactants = "reactants",
+        Products = "products",
+
+        // Either a number or a name; see symbol::Symbol.
+        DomainIdValue(Symbol),
+        StrandIdValue(StrandTag),
+        LengthValue(NucleotideLength),
+        LocationValue(Location),
+        RuleName(RuleName),
     }
 }
 
 /// domain-id nodes and strand-cell nodes should never be unified!
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Value {
     /// The value taken on by a domain-id node.
     DomainIdValue(DomainId),
@@ -73,228 +535,637 @@ pub enum Value {
     /// bottom strand; the top strand's values are all complementary to the
     /// bottom strand.
     DoubleStrandCellValue(Vec<Domain>),
+    /// The value taken on by a strand-id node.
+    StrandIdValue(StrandIdValue),
+    /// The value taken on by a length node, in nucleotides.
+    LengthValue(usize),
+    /// A strand instance's value is its id and its domains.
+    StrandValue {
+        id: StrandIdValue,
+        domains: Vec<Domain>,
+    },
+    /// The value taken on by a location node.
+    LocationValue(Location),
+    /// A pairing's value, before its complex has checked it's on the member
+    /// strands.
+    PairingValue(Pairing),
+    /// A complex's value is its member strands, sorted, and its pairings,
+    /// merged into one per helix, each read from the side nearer the start
+    /// of the complex, and then sorted. Identical strands are ordered to give the smallest pairings,
+    /// so that the same species has the same value however it's listed.
+    ComplexValue {
+        strands: Vec<Vec<Domain>>,
+        pairings: Vec<Pairing>,
+    },
+    /// The value taken on by a rule name node.
+    RuleNameValue(String),
+    /// A reactants or products node's value is its complexes' values, sorted.
+    ReactionSideValue(Vec<Value>),
+    /// A reaction's value is the name of the rule which found it, and the
+    /// values of its reactants and products.
+    ReactionValue {
+        rule: String,
+        reactants: Vec<Value>,
+        products: Vec<Value>,
+    },
 }
+/// Why an enode couldn't be given a value: e.g. a double-strand-cell over
+/// strands which aren't complementary.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MetaErrorKind {
+    /// The enode has the wrong number of children.
+    WrongArity { expected: usize },
+    /// The enode's children don't have the kinds of values it takes.
+    UnexpectedChildValues,
+    /// The two strands of a double-strand-cell aren't complementary.
+    NotComplementary,
+    /// A hairpin's loop has no domains.
+    EmptyLoop,
+    /// A double strand has no domains.
+    EmptyDoubleStrand,
+    /// A complex has no member strands.
+    NoStrands,
+    /// A complex's pairing doesn't match the member strands at its locations.
+    PairingNotOnStrands(Box<Pairing>),
+    /// A domain of a complex is bound by more than one of its pairings.
+    OverlappingPairings,
+    /// A complex's pairings don't hold all of its member strands together.
+    Disconnected,
+}
+
+/// An ill-formed enode, along with the values of its children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaError {
+    pub enode: ENode<Language>,
+    pub child_values: Vec<Option<Value>>,
+    pub kind: MetaErrorKind,
+}
+
+impl std::fmt::Display for MetaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let reason: String = match &self.kind {
+            MetaErrorKind::WrongArity { expected } => format!(
+                "expected {} children, found {}",
+                expected,
+                self.enode.children.len()
+            ),
+            MetaErrorKind::UnexpectedChildValues => "unexpected children".to_string(),
+            MetaErrorKind::NotComplementary => "strands aren't complementary".to_string(),
+            MetaErrorKind::EmptyLoop => "loop is empty".to_string(),
+            MetaErrorKind::EmptyDoubleStrand => "double strand is empty".to_string(),
+            MetaErrorKind::NoStrands => "no member strands".to_string(),
+            MetaErrorKind::PairingNotOnStrands(pairing) => format!(
+                "pairing [{}] at {} and {} isn't on the member strands",
+                print_domains(&pairing.domains),
+                pairing.bottom,
+                pairing.top
+            ),
+            MetaErrorKind::OverlappingPairings => "a domain is paired twice".to_string(),
+            MetaErrorKind::Disconnected => "member strands aren't all bound".to_string(),
+        };
+        write!(
+            f,
+            "Ill-formed {} node: {} (children: {:?})",
+            self.enode.op, reason, self.child_values
+        )
+    }
+}
+
+impl std::error::Error for MetaError {}
+
+/// Two values which disagree, but whose eclasses were unified anyway. The
+/// smaller is kept as the eclass's value.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MergeConflict {
+    pub kept: Value,
+    pub dropped: Value,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Meta {
     /// If this eclass contains a domain id, this will hold its value.
     value: Option<Value>,
+    /// If this eclass is ill-formed (or is built on one that is), this says
+    /// why, and value is None.
+    error: Option<MetaError>,
+    /// Every conflict found while merging into this eclass.
+    conflicts: Vec<MergeConflict>,
+}
+
+impl Meta {
+    pub fn value(&self) -> Option<&Value> {
+        self.value.as_ref()
+    }
+
+    pub fn error(&self) -> Option<&MetaError> {
+        self.error.as_ref()
+    }
+
+    pub fn conflicts(&self) -> &[MergeConflict] {
+        &self.conflicts
+    }
+}
+
+impl MetaError {
+    /// Orders errors so that merging picks the same one whichever eclass it
+    /// starts from.
+    fn merge_key(&self) -> (&MetaErrorKind, &[Option<Value>], String) {
+        (&self.kind, &self.child_values, format!("{:?}", self.enode))
+    }
+}
+
+/// The least value holding both `a` and `b`'s information, if they agree.
+/// A double strand holds everything known about either of its strands, so it
+/// joins with its bottom strand, or its top strand (the bottom strand's
+/// complement), to give the double strand.
+fn join(a: &Value, b: &Value) -> Option<Value> {
+    match (a, b) {
+        _ if a == b => Some(a.clone()),
+        (Value::DoubleStrandCellValue(bottom), Value::StrandCellValue(strand))
+        | (Value::StrandCellValue(strand), Value::DoubleStrandCellValue(bottom))
+            if strand == bottom || *strand == complementary_strand(bottom) =>
+        {
+            Some(Value::DoubleStrandCellValue(bottom.clone()))
+        }
+        _ => None,
+    }
 }
+
 impl Metadata<Language> for Meta {
-    type Error = ();
+    type Error = MetaError;
 
+    /// Combines the two eclasses' information as a join (see join()): a
+    /// missing value takes on the other's, and a double strand absorbs either
+    /// of its strands. Values which really conflict don't abort the merge; the
+    /// conflict is recorded (see find_conflicts()) and the smaller value is
+    /// kept, so that merging is commutative. An ill-formed eclass stays
+    /// ill-formed, and has no value.
     fn merge(&self, other: &Self) -> Self {
-        assert_eq!(self, other);
-        self.clone()
+        let mut conflicts: Vec<MergeConflict> = self
+            .conflicts
+            .iter()
+            .chain(other.conflicts.iter())
+            .cloned()
+            .collect();
+
+        let value: Option<Value> = match (&self.value, &other.value) {
+            (None, value) | (value, None) => value.clone(),
+            (Some(a), Some(b)) => match join(a, b) {
+                Some(joined) => Some(joined),
+                None => {
+                    log::warn!(
+                        "Merged eclasses with conflicting values {:?} and {:?}",
+                        a,
+                        b
+                    );
+                    conflicts.push(MergeConflict {
+                        kept: std::cmp::min(a, b).clone(),
+                        dropped: std::cmp::max(a, b).clone(),
+                    });
+                    Some(std::cmp::min(a, b).clone())
+                }
+            },
+        };
+        conflicts.sort();
+        conflicts.dedup();
+
+        let error: Option<MetaError> = match (&self.error, &other.error) {
+            (None, error) | (error, None) => error.clone(),
+            (Some(a), Some(b)) => Some(if a.merge_key() <= b.merge_key() {
+                a.clone()
+            } else {
+                b.clone()
+            }),
+        };
+
+        Meta {
+            value: if error.is_some() { None } else { value },
+            error: error,
+            conflicts: conflicts,
+        }
     }
 
     fn make(egraph: &EGraph<Language, Self>, enode: &ENode<Language>) -> Self {
-        match &enode.op {
-            Language::Nil => Meta { value: None },
-            Language::DomainIdValue(id) => Meta {
-                value: Some(Value::DomainIdValue(DomainId::DomainId(*id))),
+        // Ill-formed children make ill-formed parents; pass on the original
+        // error, which points at the enode at fault.
+        if let Some(error) = enode
+            .children
+            .iter()
+            .find_map(|child: &Id| egraph[*child].metadata.error.clone())
+        {
+            return Meta {
+                value: None,
+                error: Some(error),
+                conflicts: vec![],
+            };
+        }
+
+        match make_value(egraph, enode) {
+            Ok(value) => Meta {
+                value: value,
+                error: None,
+                conflicts: vec![],
+            },
+            Err(kind) => Meta {
+                value: None,
+                error: Some(MetaError {
+                    enode: enode.clone(),
+                    child_values: enode
+                        .children
+                        .iter()
+                        .map(|child: &Id| egraph[*child].metadata.value.clone())
+                        .collect(),
+                    kind: kind,
+                }),
+                conflicts: vec![],
             },
-            Language::Domain => {
-                assert_eq!(enode.children.len(), 1);
-                Meta {
-                    value: Some(
-                        match egraph[enode.children[0]].metadata.value.as_ref().unwrap() {
-                            Value::DomainValue(v) => Value::DomainValue(v.clone()),
-                            _ => panic!(),
-                        },
-                    ),
+        }
+    }
+}
+
+fn make_value(
+    egraph: &EGraph<Language, Meta>,
+    enode: &ENode<Language>,
+) -> Result<Option<Value>, MetaErrorKind> {
+    let expect_arity = |expected: usize| {
+        if enode.children.len() == expected {
+            Ok(())
+        } else {
+            Err(MetaErrorKind::WrongArity { expected: expected })
+        }
+    };
+    let child_value = |i: usize| egraph[enode.children[i]].metadata.value.as_ref();
+
+    match &enode.op {
+        Language::Nil => Ok(None),
+        Language::DomainIdValue(symbol) => Ok(Some(Value::DomainIdValue((*symbol).into()))),
+        Language::StrandIdValue(id) => Ok(Some(Value::StrandIdValue(id.0))),
+        Language::LengthValue(length) => Ok(Some(Value::LengthValue(length.0))),
+        Language::LocationValue(location) => Ok(Some(Value::LocationValue(*location))),
+        Language::RuleName(name) => Ok(Some(Value::RuleNameValue(name.0.clone()))),
+        Language::Reactants | Language::Products => {
+            let mut complexes: Vec<Value> = Vec::default();
+            for i in 0..enode.children.len() {
+                match child_value(i) {
+                    Some(value @ Value::ComplexValue { .. }) => complexes.push(value.clone()),
+                    _ => return Err(MetaErrorKind::UnexpectedChildValues),
                 }
             }
-            Language::LongDomain => {
-                assert_eq!(enode.children.len(), 1);
-                Meta {
-                    value: Some(
-                        match egraph[enode.children[0]].metadata.value.as_ref().unwrap() {
-                            Value::DomainIdValue(v) => Value::DomainValue(Domain::Long(v.clone())),
-                            _ => panic!(),
-                        },
-                    ),
-                }
+            complexes.sort();
+            Ok(Some(Value::ReactionSideValue(complexes)))
+        }
+        Language::Reaction => {
+            expect_arity(3)?;
+            match (child_value(0), child_value(1), child_value(2)) {
+                (
+                    Some(Value::RuleNameValue(rule)),
+                    Some(Value::ReactionSideValue(reactants)),
+                    Some(Value::ReactionSideValue(products)),
+                ) => Ok(Some(Value::ReactionValue {
+                    rule: rule.clone(),
+                    reactants: reactants.clone(),
+                    products: products.clone(),
+                })),
+                _ => Err(MetaErrorKind::UnexpectedChildValues),
             }
-            Language::ToeholdDomain => {
-                assert_eq!(enode.children.len(), 1);
-                Meta {
-                    value: Some(
-                        match egraph[enode.children[0]].metadata.value.as_ref().unwrap() {
-                            Value::DomainIdValue(v) => {
-                                Value::DomainValue(Domain::Toehold(v.clone()))
-                            }
-                            _ => panic!(),
-                        },
-                    ),
+        }
+        Language::Strand => {
+            expect_arity(2)?;
+            match (child_value(0), child_value(1)) {
+                (Some(Value::StrandIdValue(id)), Some(Value::StrandCellValue(domains))) => {
+                    Ok(Some(Value::StrandValue {
+                        id: *id,
+                        domains: domains.clone(),
+                    }))
                 }
+                _ => Err(MetaErrorKind::UnexpectedChildValues),
+            }
+        }
+        Language::Domain => {
+            expect_arity(1)?;
+            match child_value(0) {
+                Some(Value::DomainValue(v)) => Ok(Some(Value::DomainValue(v.clone()))),
+                _ => Err(MetaErrorKind::UnexpectedChildValues),
             }
-            Language::DomainId => {
-                assert_eq!(enode.children.len(), 1);
-                Meta {
-                    value: Some(
-                        match egraph[enode.children[0]].metadata.value.as_ref().unwrap() {
-                            Value::DomainIdValue(v) => Value::DomainIdValue(v.clone()),
-                            _ => panic!(),
-                        },
-                    ),
+        }
+        Language::LongDomain | Language::ToeholdDomain => {
+            let default_length: usize = if enode.op == Language::LongDomain {
+                DEFAULT_LONG_LENGTH
+            } else {
+                DEFAULT_TOEHOLD_LENGTH
+            };
+            let length: usize = match enode.children.len() {
+                1 => default_length,
+                2 => match child_value(1) {
+                    Some(Value::LengthValue(length)) => *length,
+                    _ => return Err(MetaErrorKind::UnexpectedChildValues),
+                },
+                _ => return Err(MetaErrorKind::WrongArity { expected: 2 }),
+            };
+            match child_value(0) {
+                Some(Value::DomainIdValue(v)) if enode.op == Language::LongDomain => {
+                    Ok(Some(Value::DomainValue(Domain::Long(v.clone(), length))))
+                }
+                Some(Value::DomainIdValue(v)) => {
+                    Ok(Some(Value::DomainValue(Domain::Toehold(v.clone(), length))))
                 }
+                _ => Err(MetaErrorKind::UnexpectedChildValues),
+            }
+        }
+        Language::DomainId => {
+            expect_arity(1)?;
+            match child_value(0) {
+                Some(Value::DomainIdValue(v)) => Ok(Some(Value::DomainIdValue(v.clone()))),
+                _ => Err(MetaErrorKind::UnexpectedChildValues),
             }
-            Language::Complement => {
-                assert_eq!(enode.children.len(), 1);
-                Meta {
-                    value: Some(
-                        match egraph[enode.children[0]].metadata.value.as_ref().unwrap() {
-                            Value::DomainIdValue(DomainId::Complement(domain_id_box)) => {
-                                Value::DomainIdValue(DomainId::DomainId(match **domain_id_box {
-                                    DomainId::Complement(_) => panic!(),
-                                    DomainId::DomainId(v) => v,
-                                }))
-                            }
-                            Value::DomainIdValue(DomainId::DomainId(domain_id)) => {
-                                Value::DomainIdValue(DomainId::Complement(Box::new(
-                                    DomainId::DomainId(*domain_id),
-                                )))
-                            }
-                            _ => panic!(),
-                        },
-                    ),
+        }
+        Language::Complement => {
+            expect_arity(1)?;
+            match child_value(0) {
+                Some(Value::DomainIdValue(DomainId::Complement(domain_id_box))) => {
+                    match **domain_id_box {
+                        DomainId::Complement(_) => Err(MetaErrorKind::UnexpectedChildValues),
+                        _ => Ok(Some(Value::DomainIdValue((**domain_id_box).clone()))),
+                    }
                 }
+                Some(Value::DomainIdValue(domain_id)) => Ok(Some(Value::DomainIdValue(
+                    DomainId::Complement(Box::new(domain_id.clone())),
+                ))),
+                _ => Err(MetaErrorKind::UnexpectedChildValues),
             }
-            // At first I thought StrandCells should take on the value of the
-            // domain they contain. I'm still unsure whether we should do this,
-            // but it might take more thought to implement, because it'd be hard
-            // to tease apart which is the domain and which is the other strand
-            // cell. For now I'm going to see what happens if StrandCells don't
-            // get a domain value.
-            Language::StrandCell => {
-                assert_eq!(enode.children.len(), 2);
-
-                Meta {
-                    value: match (
-                        egraph[enode.children[0]].metadata.value.as_ref(),
-                        egraph[enode.children[1]].metadata.value.as_ref(),
-                    ) {
-                        (Some(Value::DomainValue(v)), None)
-                        | (None, Some(Value::DomainValue(v))) => {
-                            Some(Value::StrandCellValue(vec![v.clone()]))
-                        }
-                        (Some(Value::DomainValue(v)), Some(Value::StrandCellValue(s))) => {
-                            Some(Value::StrandCellValue(
-                                std::iter::once(v).chain(s.iter()).cloned().collect(),
-                            ))
-                        }
-                        (Some(Value::StrandCellValue(s)), Some(Value::DomainValue(v))) => {
-                            Some(Value::StrandCellValue(
-                                s.iter().chain(std::iter::once(v)).cloned().collect(),
-                            ))
-                        }
-                        (None, None) => Some(Value::StrandCellValue(vec![])),
-                        _ => panic!(
-                            "Unexpected combination of metadata:\n{:?}\n{:?}",
-                            egraph[enode.children[0]].metadata.value.as_ref(),
-                            egraph[enode.children[1]].metadata.value.as_ref()
-                        ),
-                    },
+        }
+        // At first I thought StrandCells should take on the value of the
+        // domain they contain. I'm still unsure whether we should do this,
+        // but it might take more thought to implement, because it'd be hard
+        // to tease apart which is the domain and which is the other strand
+        // cell. For now I'm going to see what happens if StrandCells don't
+        // get a domain value.
+        Language::StrandCell => {
+            expect_arity(2)?;
+
+            match (child_value(0), child_value(1)) {
+                (Some(Value::DomainValue(v)), None) | (None, Some(Value::DomainValue(v))) => {
+                    Ok(Some(Value::StrandCellValue(vec![v.clone()])))
                 }
+                (Some(Value::DomainValue(v)), Some(Value::StrandCellValue(s))) => Ok(Some(
+                    Value::StrandCellValue(std::iter::once(v).chain(s.iter()).cloned().collect()),
+                )),
+                (Some(Value::StrandCellValue(s)), Some(Value::DomainValue(v))) => Ok(Some(
+                    Value::StrandCellValue(s.iter().chain(std::iter::once(v)).cloned().collect()),
+                )),
+                (None, None) => Ok(Some(Value::StrandCellValue(vec![]))),
+                _ => Err(MetaErrorKind::UnexpectedChildValues),
             }
-            Language::DoubleStrandCell => {
-                assert_eq!(enode.children.len(), 3);
-
-                match (
-                    egraph[enode.children[0]].metadata.value.as_ref(),
-                    egraph[enode.children[1]].metadata.value.as_ref(),
-                    egraph[enode.children[2]].metadata.value.as_ref(),
-                ) {
-                    (
-                        Some(Value::StrandCellValue(bottom)),
-                        Some(Value::StrandCellValue(top)),
-                        None,
-                    )
-                    | (
-                        None,
-                        Some(Value::StrandCellValue(bottom)),
-                        Some(Value::StrandCellValue(top)),
-                    )
-                    | (
-                        Some(Value::DoubleStrandCellValue(_)),
-                        Some(Value::StrandCellValue(bottom)),
-                        Some(Value::StrandCellValue(top)),
-                    )
-                    | (
-                        Some(Value::StrandCellValue(bottom)),
-                        Some(Value::StrandCellValue(top)),
-                        Some(Value::DoubleStrandCellValue(_)),
-                    ) => {
-                        // Check that we have two complementary strand cells as arguments.
-                        println!("{:?}", bottom);
-                        assert!(bottom
-                            .iter()
-                            .map(|d: &Domain| {
-                                match d {
-                                    Domain::Long(v) => {
-                                        Domain::Long(DomainId::Complement(Box::new(v.clone())))
-                                    }
-                                    Domain::Toehold(v) => {
-                                        Domain::Toehold(DomainId::Complement(Box::new(v.clone())))
-                                    }
-                                }
-                            })
-                            .rev()
-                            .eq(top.iter().cloned()));
-                        Meta {
-                            value: Some(Value::DoubleStrandCellValue(bottom.clone())),
-                        }
+        }
+        Language::DoubleStrandCell => {
+            expect_arity(3)?;
+
+            match (child_value(0), child_value(1), child_value(2)) {
+                (Some(Value::StrandCellValue(bottom)), Some(Value::StrandCellValue(top)), None)
+                | (None, Some(Value::StrandCellValue(bottom)), Some(Value::StrandCellValue(top)))
+                | (
+                    Some(Value::DoubleStrandCellValue(_)),
+                    Some(Value::StrandCellValue(bottom)),
+                    Some(Value::StrandCellValue(top)),
+                )
+                | (
+                    Some(Value::StrandCellValue(bottom)),
+                    Some(Value::StrandCellValue(top)),
+                    Some(Value::DoubleStrandCellValue(_)),
+                ) => {
+                    // Check that we have two complementary strand cells as arguments.
+                    if bottom
+                        .iter()
+                        .map(Domain::complement)
+                        .rev()
+                        .eq(top.iter().cloned())
+                    {
+                        Ok(Some(Value::DoubleStrandCellValue(bottom.clone())))
+                    } else {
+                        Err(MetaErrorKind::NotComplementary)
                     }
-                    _ => panic!(),
+                }
+                _ => Err(MetaErrorKind::UnexpectedChildValues),
+            }
+        }
+        Language::Pairing => {
+            expect_arity(3)?;
+
+            match (child_value(0), child_value(1), child_value(2)) {
+                (
+                    Some(Value::LocationValue(bottom)),
+                    Some(Value::LocationValue(top)),
+                    Some(Value::DoubleStrandCellValue(domains)),
+                ) => Ok(Some(Value::PairingValue(Pairing {
+                    bottom: *bottom,
+                    top: *top,
+                    domains: domains.clone(),
+                }))),
+                _ => Err(MetaErrorKind::UnexpectedChildValues),
+            }
+        }
+        Language::Complex => {
+            let mut strands: Vec<Vec<Domain>> = Vec::default();
+            let mut pairings: Vec<Pairing> = Vec::default();
+            for i in 0..enode.children.len() {
+                match child_value(i) {
+                    Some(Value::StrandCellValue(s))
+                    | Some(Value::StrandValue { domains: s, .. }) => strands.push(s.clone()),
+                    Some(Value::PairingValue(pairing)) => pairings.push(pairing.clone()),
+                    _ => return Err(MetaErrorKind::UnexpectedChildValues),
                 }
             }
+
+            let (order, pairings): (Vec<usize>, Vec<Pairing>) =
+                canonical_complex(&strands, &pairings)?;
+            Ok(Some(Value::ComplexValue {
+                strands: order.iter().map(|i: &usize| strands[*i].clone()).collect(),
+                pairings: pairings,
+            }))
         }
     }
 }
 
-/// Strand values should be in bottom strand direction.
-/// TODO(gus) put this in terms of 3' or 5' ends
-/// Returns the id of the strand.
-pub fn add_strand_to_egraph(
-    egraph: &mut EGraph<Language, Meta>,
-    strand_values: &Vec<Domain>,
-) -> Id {
-    fn add_domain_id_to_egraph(egraph: &mut EGraph<Language, Meta>, domain_id: &DomainId) -> Id {
-        match &domain_id {
-            &DomainId::Complement(domain_id) => {
-                let domain_id_egraph_id: Id = add_domain_id_to_egraph(egraph, domain_id);
-                egraph.add(ENode::new(Language::Complement, vec![domain_id_egraph_id]))
+/// Checks that `pairings` bind `strands` into a single complex: each must
+/// match the strands at its locations, no domain may be bound twice, no loop
+/// may be empty, and every strand must be bound to the rest. Returns the order
+/// to list the strands in (as indices into `strands`) and the pairings as
+/// they are in that order: see Value::ComplexValue.
+fn canonical_complex(
+    strands: &[Vec<Domain>],
+    pairings: &[Pairing],
+) -> Result<(Vec<usize>, Vec<Pairing>), MetaErrorKind> {
+    if strands.is_empty() {
+        return Err(MetaErrorKind::NoStrands);
+    }
+
+    let mut bound: std::collections::HashSet<Location> = std::collections::HashSet::default();
+    // Which strands are bound together, as the lowest strand each is bound to.
+    let mut component: Vec<usize> = (0..strands.len()).collect();
+    for pairing in pairings.iter() {
+        let top_domains: Vec<Domain> = complementary_strand(&pairing.domains);
+        for (location, domains) in &[
+            (pairing.bottom, &pairing.domains),
+            (pairing.top, &top_domains),
+        ] {
+            let on_strand: bool = !domains.is_empty()
+                && strands
+                    .get(location.strand)
+                    .and_then(|strand: &Vec<Domain>| {
+                        strand.get(location.domain..location.domain + domains.len())
+                    })
+                    == Some(domains.as_slice());
+            if !on_strand {
+                return Err(MetaErrorKind::PairingNotOnStrands(Box::new(
+                    pairing.clone(),
+                )));
             }
-            &DomainId::DomainId(id) => {
-                let domain_id_value_egraph_id: Id =
-                    egraph.add(ENode::leaf(Language::DomainIdValue(*id)));
-                egraph.add(ENode::new(
-                    Language::DomainId,
-                    vec![domain_id_value_egraph_id],
-                ))
+            for i in 0..domains.len() {
+                if !bound.insert(Location {
+                    strand: location.strand,
+                    domain: location.domain + i,
+                }) {
+                    return Err(MetaErrorKind::OverlappingPairings);
+                }
             }
         }
-    }
 
-    fn add_domain_to_egraph(egraph: &mut EGraph<Language, Meta>, domain: &Domain) -> Id {
-        match &domain {
-            &Domain::Toehold(id) => {
-                let domain_id_enode_id: Id = add_domain_id_to_egraph(egraph, id);
-                let toehold_eclass_id: Id = egraph.add(ENode::new(
-                    Language::ToeholdDomain,
-                    vec![domain_id_enode_id],
-                ));
-                egraph.add(ENode::new(Language::Domain, vec![toehold_eclass_id]))
+        // A strand can't turn around without any loop at all.
+        let first: Location = std::cmp::min(pairing.bottom, pairing.top);
+        let second: Location = std::cmp::max(pairing.bottom, pairing.top);
+        if first.strand == second.strand && first.domain + pairing.domains.len() == second.domain {
+            return Err(MetaErrorKind::EmptyLoop);
+        }
+
+        let (from, to): (usize, usize) = (
+            component[pairing.bottom.strand],
+            component[pairing.top.strand],
+        );
+        for c in component.iter_mut() {
+            if *c == std::cmp::max(from, to) {
+                *c = std::cmp::min(from, to);
             }
-            &Domain::Long(id) => {
-                let domain_id_enode_id: Id = add_domain_id_to_egraph(egraph, id);
-                let long_eclass_id: Id =
-                    egraph.add(ENode::new(Language::LongDomain, vec![domain_id_enode_id]));
-                egraph.add(ENode::new(Language::Domain, vec![long_eclass_id]))
+        }
+    }
+    if component.iter().any(|c: &usize| *c != 0) {
+        return Err(MetaErrorKind::Disconnected);
+    }
+
+    // Sort the strands, then try every ordering of identical strands, keeping
+    // whichever gives the smallest pairings. Complexes rarely hold more than
+    // a few copies of a strand, so there are few orderings to try.
+    let mut order: Vec<usize> = (0..strands.len()).collect();
+    order.sort_by(|a: &usize, b: &usize| strands[*a].cmp(&strands[*b]));
+    let mut orders: Vec<Vec<usize>> = vec![vec![]];
+    let mut start: usize = 0;
+    while start < order.len() {
+        let end: usize = start
+            + order[start..]
+                .iter()
+                .take_while(|i: &&usize| strands[**i] == strands[order[start]])
+                .count();
+        orders = orders
+            .into_iter()
+            .flat_map(|prefix: Vec<usize>| {
+                permutations(&order[start..end])
+                    .into_iter()
+                    .map(move |group: Vec<usize>| prefix.iter().cloned().chain(group).collect())
+            })
+            .collect();
+        start = end;
+    }
+
+    let (pairings, order): (Vec<Pairing>, Vec<usize>) = orders
+        .into_iter()
+        .map(|order: Vec<usize>| {
+            let mut position: Vec<usize> = vec![0; order.len()];
+            for (i, strand) in order.iter().enumerate() {
+                position[*strand] = i;
             }
+            let relocate = |location: Location| Location {
+                strand: position[location.strand],
+                domain: location.domain,
+            };
+            let ordered_strands: Vec<Vec<Domain>> =
+                order.iter().map(|i: &usize| strands[*i].clone()).collect();
+            // Pairings which continue one another are merged, so that the
+            // same helix is always one pairing.
+            let relocated_pairs: Vec<(Location, Location)> = pairings
+                .iter()
+                .flat_map(|pairing: &Pairing| pairing.domain_pairs())
+                .map(|(a, b): (Location, Location)| (relocate(a), relocate(b)))
+                .collect();
+            (
+                pairings_from_pairs(&ordered_strands, &relocated_pairs),
+                order,
+            )
+        })
+        .min()
+        .ok_or(MetaErrorKind::NoStrands)?;
+    Ok((order, pairings))
+}
+
+/// Every ordering of `items`.
+fn permutations(items: &[usize]) -> Vec<Vec<usize>> {
+    if items.is_empty() {
+        return vec![vec![]];
+    }
+    (0..items.len())
+        .flat_map(|i: usize| {
+            let rest: Vec<usize> = items[..i]
+                .iter()
+                .chain(items[i + 1..].iter())
+                .cloned()
+                .collect();
+            permutations(&rest)
+                .into_iter()
+                .map(move |mut permutation: Vec<usize>| {
+                    permutation.insert(0, items[i]);
+                    permutation
+                })
+        })
+        .collect()
+}
+
+fn add_domain_id_to_egraph(egraph: &mut EGraph<Language, Meta>, domain_id: &DomainId) -> Id {
+    match &domain_id {
+        &DomainId::Complement(domain_id) => {
+            let domain_id_egraph_id: Id = add_domain_id_to_egraph(egraph, domain_id);
+            egraph.add(ENode::new(Language::Complement, vec![domain_id_egraph_id]))
         }
+        &DomainId::DomainId(_) | &DomainId::Named(_) => {
+            let domain_id_value_egraph_id: Id =
+                egraph.add(ENode::leaf(Language::DomainIdValue(domain_id.symbol())));
+            egraph.add(ENode::new(
+                Language::DomainId,
+                vec![domain_id_value_egraph_id],
+            ))
+        }
+    }
+}
+
+/// Domains of the default length are added without a length, so that
+/// patterns which don't mention lengths match them.
+fn add_domain_to_egraph(egraph: &mut EGraph<Language, Meta>, domain: &Domain) -> Id {
+    let op: Language = match domain {
+        Domain::Toehold(..) => Language::ToeholdDomain,
+        Domain::Long(..) => Language::LongDomain,
+    };
+    let mut children: Vec<Id> = vec![add_domain_id_to_egraph(egraph, domain.id())];
+    if !domain.has_default_length() {
+        children.push(
+            egraph.add(ENode::leaf(Language::LengthValue(NucleotideLength(
+                domain.length(),
+            )))),
+        );
     }
+    let kind_eclass_id: Id = egraph.add(ENode::new(op, children));
+    egraph.add(ENode::new(Language::Domain, vec![kind_eclass_id]))
+}
 
+/// Adds the strand cells for a strand without rebuilding the egraph, so that
+/// it can be used from within appliers.
+fn add_strand_cells_to_egraph(egraph: &mut EGraph<Language, Meta>, strand_values: &[Domain]) -> Id {
     let nil_eclass_id = egraph.add(ENode::leaf(Language::Nil));
 
     let domain_eclass_ids: Vec<Id> = strand_values
@@ -304,7 +1175,7 @@ pub fn add_strand_to_egraph(
         .map(|domain: &Domain| add_domain_to_egraph(egraph, domain))
         .collect();
 
-    let id: Id = domain_eclass_ids
+    domain_eclass_ids
         .iter()
         // Then, over the list of domain IDs, we construct a tree of strand
         // cells, which for domains [d0, d1, d2] will look something like:
@@ -326,11 +1197,381 @@ pub fn add_strand_to_egraph(
                     vec![previous_strand_cell_eclass_id, *domain_eclass_id],
                 ))
             },
-        );
+        )
+}
+
+/// Returns `id`, or the error if its eclass turned out to be ill-formed.
+fn check_eclass(egraph: &EGraph<Language, Meta>, id: Id) -> Result<Id, MetaError> {
+    match egraph[id].metadata.error.as_ref() {
+        Some(error) => Err(error.clone()),
+        None => Ok(id),
+    }
+}
+
+/// Finds an ill-formed eclass anywhere in `egraph`, e.g. one added with
+/// EGraph::add() directly, and returns why it's ill-formed.
+pub fn find_error(egraph: &EGraph<Language, Meta>) -> Option<MetaError> {
+    egraph
+        .classes()
+        .find_map(|eclass| eclass.metadata.error.clone())
+}
+
+/// Every conflict recorded while merging eclasses in `egraph`, along with the
+/// eclass it was recorded in.
+pub fn find_conflicts(egraph: &EGraph<Language, Meta>) -> Vec<(Id, MergeConflict)> {
+    egraph
+        .classes()
+        .flat_map(|eclass| {
+            eclass
+                .metadata
+                .conflicts
+                .iter()
+                .map(move |conflict: &MergeConflict| (eclass.id, conflict.clone()))
+        })
+        .collect()
+}
+
+// The add_*_to_egraph() functions below fail if what they're asked to add is
+// ill-formed, e.g. a complex whose pairings aren't on its strands. Whatever
+// was added before the problem was found is left in the egraph.
+
+/// Strand values should be in bottom strand direction.
+/// TODO(gus) put this in terms of 3' or 5' ends
+/// The strand is also added as a species on its own (a complex with it as its
+/// only member), so that reaction rules see it.
+/// Returns the id of the strand.
+pub fn add_strand_to_egraph(
+    egraph: &mut EGraph<Language, Meta>,
+    strand_values: &Vec<Domain>,
+) -> Result<Id, MetaError> {
+    let id: Id = add_strand_cells_to_egraph(egraph, strand_values);
+    add_complex_cells_to_egraph(egraph, &[id], std::slice::from_ref(strand_values), &[]);
+
+    egraph.rebuild();
+
+    check_eclass(egraph, id)
+}
+
+/// Adds strand instance `strand_id`, made up of `strand_values` (in bottom
+/// strand direction). Unlike add_strand_to_egraph(), adding the same domains
+/// under two ids gives two eclasses, which can then bind to each other. Like
+/// add_strand_to_egraph(), the instance is also added as a species on its own.
+/// Returns the id of the strand instance.
+pub fn add_strand_instance_to_egraph(
+    egraph: &mut EGraph<Language, Meta>,
+    strand_id: StrandIdValue,
+    strand_values: &Vec<Domain>,
+) -> Result<Id, MetaError> {
+    let strand_cell_eclass_id: Id = add_strand_cells_to_egraph(egraph, strand_values);
+    let strand_id_eclass_id: Id =
+        egraph.add(ENode::leaf(Language::StrandIdValue(StrandTag(strand_id))));
+    let id: Id = egraph.add(ENode::new(
+        Language::Strand,
+        vec![strand_id_eclass_id, strand_cell_eclass_id],
+    ));
+    add_complex_cells_to_egraph(egraph, &[id], std::slice::from_ref(strand_values), &[]);
+
+    egraph.rebuild();
+
+    check_eclass(egraph, id)
+}
+
+/// Adds an already-formed double strand, with `bottom_strand_values` in bottom
+/// strand direction bound to their complement. The double strand cells are
+/// chained up in the same shape that toehold_bind() and bind() produce them in,
+/// starting from the first domain.
+/// Returns the id of the outermost double strand cell. Fails, adding
+/// nothing, if `bottom_strand_values` is empty.
+pub fn add_double_strand_to_egraph(
+    egraph: &mut EGraph<Language, Meta>,
+    bottom_strand_values: &Vec<Domain>,
+) -> Result<Id, MetaError> {
+    if bottom_strand_values.is_empty() {
+        return Err(MetaError {
+            enode: ENode::new(Language::DoubleStrandCell, vec![]),
+            child_values: vec![],
+            kind: MetaErrorKind::EmptyDoubleStrand,
+        });
+    }
+
+    let id: Id = add_double_strand_cells_to_egraph(egraph, bottom_strand_values);
+
+    egraph.rebuild();
+
+    check_eclass(egraph, id)
+}
+
+/// Adds the double strand cells for add_double_strand_to_egraph(), without
+/// rebuilding, so that rules can use it. Gives nil if `bottom_strand_values`
+/// is empty.
+fn add_double_strand_cells_to_egraph(
+    egraph: &mut EGraph<Language, Meta>,
+    bottom_strand_values: &[Domain],
+) -> Id {
+    let nil_eclass_id = egraph.add(ENode::leaf(Language::Nil));
+
+    let mut bottom_strand_cell_eclass_id: Id = nil_eclass_id;
+    let mut top_strand_cell_eclass_id: Id = nil_eclass_id;
+    let mut double_strand_cell_eclass_id: Id = nil_eclass_id;
+    for domain in bottom_strand_values.iter() {
+        let bottom_domain_eclass_id: Id = add_domain_to_egraph(egraph, domain);
+        let top_domain_eclass_id: Id = add_domain_to_egraph(egraph, &domain.complement());
+
+        // Bottom strand cells grow to the right, top strand cells to the left,
+        // so that the top strand cell always reads as the complement of the
+        // bottom strand cell.
+        bottom_strand_cell_eclass_id = egraph.add(ENode::new(
+            Language::StrandCell,
+            vec![bottom_strand_cell_eclass_id, bottom_domain_eclass_id],
+        ));
+        top_strand_cell_eclass_id = egraph.add(ENode::new(
+            Language::StrandCell,
+            vec![top_domain_eclass_id, top_strand_cell_eclass_id],
+        ));
+
+        double_strand_cell_eclass_id = if double_strand_cell_eclass_id == nil_eclass_id {
+            egraph.add(ENode::new(
+                Language::DoubleStrandCell,
+                vec![
+                    bottom_strand_cell_eclass_id,
+                    top_strand_cell_eclass_id,
+                    nil_eclass_id,
+                ],
+            ))
+        } else {
+            egraph.add(ENode::new(
+                Language::DoubleStrandCell,
+                vec![
+                    double_strand_cell_eclass_id,
+                    bottom_strand_cell_eclass_id,
+                    top_strand_cell_eclass_id,
+                ],
+            ))
+        };
+    }
+
+    double_strand_cell_eclass_id
+}
+
+/// Adds a hairpin: a strand made up of `stem_values`, then `loop_values`, then
+/// the complement of `stem_values`, with its two ends bound to each other.
+/// `stem_values` are in bottom strand direction, i.e. in the order they appear
+/// at the start of the strand. A hairpin is just a one-strand complex, so
+/// it's the same species as hairpin_formation() folds.
+/// Returns the id of the hairpin.
+pub fn add_hairpin_to_egraph(
+    egraph: &mut EGraph<Language, Meta>,
+    stem_values: &Vec<Domain>,
+    loop_values: &Vec<Domain>,
+) -> Result<Id, MetaError> {
+    let strand_values: Vec<Domain> = stem_values
+        .iter()
+        .chain(loop_values.iter())
+        .cloned()
+        .chain(complementary_strand(stem_values))
+        .collect();
+    let stem: Pairing = Pairing {
+        bottom: Location {
+            strand: 0,
+            domain: 0,
+        },
+        top: Location {
+            strand: 0,
+            domain: stem_values.len() + loop_values.len(),
+        },
+        domains: stem_values.clone(),
+    };
+
+    add_complex_to_egraph(egraph, &vec![strand_values], &vec![stem])
+}
+
+/// Adds `pairing` (its domains as a double strand), without rebuilding; it
+/// only makes sense as a child of a complex node.
+fn add_pairing_cells_to_egraph(egraph: &mut EGraph<Language, Meta>, pairing: &Pairing) -> Id {
+    let bottom_eclass_id: Id = egraph.add(ENode::leaf(Language::LocationValue(pairing.bottom)));
+    let top_eclass_id: Id = egraph.add(ENode::leaf(Language::LocationValue(pairing.top)));
+    let double_strand_eclass_id: Id = add_double_strand_cells_to_egraph(egraph, &pairing.domains);
+    egraph.add(ENode::new(
+        Language::Pairing,
+        vec![bottom_eclass_id, top_eclass_id, double_strand_eclass_id],
+    ))
+}
+
+/// Adds a complex made up of the strands `strand_values`, bound together by
+/// `pairing_values`, whose locations index into `strand_values`. The complex
+/// is added in the order its value lists it in, so the same species lands in
+/// the same eclass however its strands are listed.
+/// Returns the id of the complex.
+pub fn add_complex_to_egraph(
+    egraph: &mut EGraph<Language, Meta>,
+    strand_values: &Vec<Vec<Domain>>,
+    pairing_values: &Vec<Pairing>,
+) -> Result<Id, MetaError> {
+    let member_eclass_ids: Vec<Id> = strand_values
+        .iter()
+        .map(|strand: &Vec<Domain>| add_strand_cells_to_egraph(egraph, strand))
+        .collect();
+    let id: Id =
+        add_complex_cells_to_egraph(egraph, &member_eclass_ids, strand_values, pairing_values);
 
     egraph.rebuild();
 
-    id
+    check_eclass(egraph, id)
+}
+
+/// Adds the complex for add_complex_to_egraph(), without rebuilding, so that
+/// rules can use it. `members` are the eclasses of the member strands (strand
+/// cells or strand instances), whose domains are `strand_values`. Members with
+/// the same domains are kept in the order of their eclasses, so that the same
+/// members always give the same complex node.
+fn add_complex_cells_to_egraph(
+    egraph: &mut EGraph<Language, Meta>,
+    members: &[Id],
+    strand_values: &[Vec<Domain>],
+    pairing_values: &[Pairing],
+) -> Id {
+    let mut sorted: Vec<usize> = (0..members.len()).collect();
+    sorted.sort_by_key(|i: &usize| (&strand_values[*i], egraph.find(members[*i])));
+    let mut position: Vec<usize> = vec![0; sorted.len()];
+    for (i, member) in sorted.iter().enumerate() {
+        position[*member] = i;
+    }
+    let sorted_strands: Vec<Vec<Domain>> = sorted
+        .iter()
+        .map(|i: &usize| strand_values[*i].clone())
+        .collect();
+    let sorted_pairings: Vec<Pairing> = pairing_values
+        .iter()
+        .map(|pairing: &Pairing| {
+            let relocate = |location: Location| Location {
+                strand: position
+                    .get(location.strand)
+                    .cloned()
+                    .unwrap_or(location.strand),
+                domain: location.domain,
+            };
+            Pairing {
+                bottom: relocate(pairing.bottom),
+                top: relocate(pairing.top),
+                domains: pairing.domains.clone(),
+            }
+        })
+        .collect();
+
+    // An ill-formed complex is added as it's given, so that its eclass says
+    // what's wrong with it.
+    let (order, pairings): (Vec<usize>, Vec<Pairing>) =
+        canonical_complex(&sorted_strands, &sorted_pairings)
+            .unwrap_or_else(|_| ((0..sorted_strands.len()).collect(), sorted_pairings.clone()));
+
+    let mut child_eclass_ids: Vec<Id> = order.iter().map(|i: &usize| members[sorted[*i]]).collect();
+    for pairing in pairings.iter() {
+        child_eclass_ids.push(add_pairing_cells_to_egraph(egraph, pairing));
+    }
+    egraph.add(ENode::new(Language::Complex, child_eclass_ids))
+}
+
+/// Adds a reaction, found by rule `rule`, from the complexes `reactants` to
+/// the complexes `products`, without rebuilding. Returns the id of the
+/// reaction.
+fn add_reaction_cells_to_egraph(
+    egraph: &mut EGraph<Language, Meta>,
+    rule: &str,
+    reactants: &[Id],
+    products: &[Id],
+) -> Id {
+    let rule_eclass_id: Id =
+        egraph.add(ENode::leaf(Language::RuleName(RuleName(rule.to_string()))));
+    let mut side_eclass_ids: Vec<Id> = Vec::default();
+    for (op, side) in &[
+        (Language::Reactants, reactants),
+        (Language::Products, products),
+    ] {
+        let mut complexes: Vec<Id> = side.iter().map(|id: &Id| egraph.find(*id)).collect();
+        complexes.sort();
+        side_eclass_ids.push(egraph.add(ENode::new(op.clone(), complexes)));
+    }
+    egraph.add(ENode::new(
+        Language::Reaction,
+        vec![rule_eclass_id, side_eclass_ids[0], side_eclass_ids[1]],
+    ))
+}
+
+/// Finds every strand instance made up of exactly `domains`, along with its
+/// strand id.
+pub fn find_strand_instances(
+    egraph: &EGraph<Language, Meta>,
+    domains: &[Domain],
+) -> Vec<(StrandIdValue, Id)> {
+    let mut instances: Vec<(StrandIdValue, Id)> = egraph
+        .classes()
+        .filter_map(|eclass| match eclass.metadata.value.as_ref() {
+            Some(Value::StrandValue {
+                id,
+                domains: instance_domains,
+            }) if instance_domains.as_slice() == domains => Some((*id, eclass.id)),
+            _ => None,
+        })
+        .collect();
+    instances.sort();
+    instances
+}
+
+/// The ids of the strand instances in `eclass`: the strand itself, if it's a
+/// strand instance, or the member strand instances of a complex. This is how
+/// a product can be traced back to the strands it was made from.
+pub fn strand_ids(egraph: &EGraph<Language, Meta>, eclass: Id) -> Vec<StrandIdValue> {
+    if let Some(Value::StrandValue { id, .. }) = egraph[eclass].metadata.value.as_ref() {
+        return vec![*id];
+    }
+    let mut ids: Vec<StrandIdValue> = egraph[eclass]
+        .nodes
+        .iter()
+        .filter(|enode| enode.op == Language::Complex)
+        .flat_map(|enode| enode.children.iter())
+        .filter_map(|child: &Id| match egraph[*child].metadata.value.as_ref() {
+            Some(Value::StrandValue { id, .. }) => Some(*id),
+            _ => None,
+        })
+        .collect();
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+/// Finds every strand cell eclass whose value is exactly `domains`.
+pub fn find_strand_cells(egraph: &EGraph<Language, Meta>, domains: &[Domain]) -> Vec<Id> {
+    find_eclasses_with_value(
+        egraph,
+        "(strand-cell ?a ?b)",
+        &Value::StrandCellValue(domains.to_vec()),
+    )
+}
+
+/// Finds every double strand cell eclass whose (bottom strand) value is
+/// exactly `domains`.
+pub fn find_double_strand_cells(egraph: &EGraph<Language, Meta>, domains: &[Domain]) -> Vec<Id> {
+    find_eclasses_with_value(
+        egraph,
+        "(double-strand-cell ?a ?b ?c)",
+        &Value::DoubleStrandCellValue(domains.to_vec()),
+    )
+}
+
+fn find_eclasses_with_value(
+    egraph: &EGraph<Language, Meta>,
+    pattern: &str,
+    value: &Value,
+) -> Vec<Id> {
+    use egg::{Pattern, SearchMatches, Searcher};
+    pattern
+        .parse::<Pattern<Language>>()
+        .unwrap()
+        .search(egraph)
+        .iter()
+        .map(|search_matches: &SearchMatches| search_matches.eclass)
+        .filter(|id: &Id| egraph[*id].metadata.value.as_ref() == Some(value))
+        .collect()
 }
 
 #[cfg(test)]
@@ -344,13 +1585,14 @@ mod tests {
         let id: Id = add_strand_to_egraph(
             &mut egraph,
             &vec![
-                Domain::Toehold(DomainId::DomainId(0)),
-                Domain::Long(DomainId::DomainId(1)),
-                Domain::Long(DomainId::DomainId(2)),
-                Domain::Long(DomainId::Complement(Box::new(DomainId::DomainId(2)))),
-                Domain::Long(DomainId::DomainId(3)),
+                Domain::toehold(DomainId::DomainId(0)),
+                Domain::long(DomainId::DomainId(1)),
+                Domain::long(DomainId::DomainId(2)),
+                Domain::long(DomainId::Complement(Box::new(DomainId::DomainId(2)))),
+                Domain::long(DomainId::DomainId(3)),
             ],
-        );
+        )
+        .unwrap();
 
         //egraph.dot().to_svg("add-to-egraph.svg").unwrap();
 
@@ -385,12 +1627,412 @@ mod tests {
                 _ => panic!(),
             },
             &[
-                Domain::Toehold(DomainId::DomainId(0)),
-                Domain::Long(DomainId::DomainId(1)),
-                Domain::Long(DomainId::DomainId(2)),
-                Domain::Long(DomainId::Complement(Box::new(DomainId::DomainId(2)))),
-                Domain::Long(DomainId::DomainId(3)),
+                Domain::toehold(DomainId::DomainId(0)),
+                Domain::long(DomainId::DomainId(1)),
+                Domain::long(DomainId::DomainId(2)),
+                Domain::long(DomainId::Complement(Box::new(DomainId::DomainId(2)))),
+                Domain::long(DomainId::DomainId(3)),
             ],
         )
     }
+
+    #[test]
+    fn add_hairpin() {
+        let mut egraph = EGraph::<Language, Meta>::default();
+        let id: Id = add_hairpin_to_egraph(
+            &mut egraph,
+            &vec![
+                Domain::toehold(DomainId::DomainId(0)),
+                Domain::long(DomainId::DomainId(1)),
+            ],
+            &vec![Domain::long(DomainId::DomainId(2))],
+        )
+        .unwrap();
+
+        assert_eq!(
+            egraph[id].metadata.value,
+            Some(Value::ComplexValue {
+                strands: vec![parse_domains("t0^ 1 2 1* t0^*").unwrap()],
+                pairings: vec![Pairing {
+                    bottom: Location {
+                        strand: 0,
+                        domain: 0,
+                    },
+                    top: Location {
+                        strand: 0,
+                        domain: 3,
+                    },
+                    domains: parse_domains("t0^ 1").unwrap(),
+                }],
+            })
+        );
+        assert_eq!(
+            find_strand_cells(
+                &egraph,
+                &[
+                    Domain::toehold(DomainId::DomainId(0)),
+                    Domain::long(DomainId::DomainId(1)),
+                    Domain::long(DomainId::DomainId(2)),
+                    Domain::long(DomainId::Complement(Box::new(DomainId::DomainId(1)))),
+                    Domain::toehold(DomainId::Complement(Box::new(DomainId::DomainId(0)))),
+                ]
+            )
+            .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn add_complex() {
+        let mut egraph = EGraph::<Language, Meta>::default();
+        let template: Vec<Domain> = vec![
+            Domain::toehold(DomainId::DomainId(0)),
+            Domain::long(DomainId::DomainId(1)),
+        ];
+        let incumbent: Vec<Domain> = vec![Domain::long(DomainId::Complement(Box::new(
+            DomainId::DomainId(1),
+        )))];
+        let pairing = |bottom: (usize, usize), top: (usize, usize), domains: &str| Pairing {
+            bottom: Location {
+                strand: bottom.0,
+                domain: bottom.1,
+            },
+            top: Location {
+                strand: top.0,
+                domain: top.1,
+            },
+            domains: parse_domains(domains).unwrap(),
+        };
+        let id: Id = add_complex_to_egraph(
+            &mut egraph,
+            &vec![template.clone(), incumbent.clone()],
+            &vec![pairing((0, 1), (1, 0), "1")],
+        )
+        .unwrap();
+        // Listing the strands in another order, or reading the pairing from
+        // its other side, gives the same species in the same eclass.
+        let other_id: Id = add_complex_to_egraph(
+            &mut egraph,
+            &vec![incumbent.clone(), template.clone()],
+            &vec![pairing((0, 0), (1, 1), "1*")],
+        )
+        .unwrap();
+
+        assert_eq!(
+            egraph[id].metadata.value,
+            Some(Value::ComplexValue {
+                strands: vec![template, incumbent],
+                pairings: vec![pairing((0, 1), (1, 0), "1")],
+            })
+        );
+        assert_eq!(egraph.find(id), egraph.find(other_id));
+
+        // Two copies of a strand bound in a ring, listed either way round.
+        let strand: Vec<Domain> = parse_domains("1 2 1* 2*").unwrap();
+        let strands: Vec<Vec<Domain>> = vec![strand.clone(), strand.clone()];
+        let id: Id = add_complex_to_egraph(
+            &mut egraph,
+            &strands,
+            &vec![pairing((0, 0), (1, 2), "1"), pairing((0, 3), (1, 1), "2*")],
+        )
+        .unwrap();
+        let other_id: Id = add_complex_to_egraph(
+            &mut egraph,
+            &strands,
+            &vec![pairing((1, 0), (0, 2), "1"), pairing((1, 3), (0, 1), "2*")],
+        )
+        .unwrap();
+        assert_eq!(egraph.find(id), egraph.find(other_id));
+        // The same strands bound differently are a different species.
+        let hairpins: Id = add_complex_to_egraph(
+            &mut egraph,
+            &strands,
+            &vec![pairing((0, 0), (1, 2), "1"), pairing((0, 1), (0, 3), "2")],
+        )
+        .unwrap();
+        assert_ne!(egraph.find(id), egraph.find(hairpins));
+        assert_ne!(egraph[id].metadata.value, egraph[hairpins].metadata.value);
+    }
+
+    #[test]
+    fn ill_formed_input() {
+        let mut egraph = EGraph::<Language, Meta>::default();
+        let error: MetaError = add_complex_to_egraph(
+            &mut egraph,
+            &vec![parse_domains("t0^ 1").unwrap()],
+            &vec![Pairing {
+                bottom: Location {
+                    strand: 0,
+                    domain: 1,
+                },
+                top: Location {
+                    strand: 0,
+                    domain: 0,
+                },
+                domains: parse_domains("1").unwrap(),
+            }],
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.kind,
+            MetaErrorKind::PairingNotOnStrands(Box::new(Pairing {
+                bottom: Location {
+                    strand: 0,
+                    domain: 1,
+                },
+                top: Location {
+                    strand: 0,
+                    domain: 0,
+                },
+                domains: parse_domains("1").unwrap(),
+            }))
+        );
+        assert_eq!(error.enode.op, Language::Complex);
+        assert_eq!(
+            add_double_strand_to_egraph(&mut egraph, &vec![])
+                .unwrap_err()
+                .kind,
+            MetaErrorKind::EmptyDoubleStrand
+        );
+        let complex_error = |strands: &str, pairings: &str| {
+            let mut egraph = EGraph::<Language, Meta>::default();
+            let expr: egg::RecExpr<Language> = format!("(complex {} {})", strands, pairings)
+                .parse()
+                .unwrap();
+            let id: Id = egraph.add_expr(&expr);
+            egraph[id].metadata.error().unwrap().kind.clone()
+        };
+        let strand: &str = "(strand-cell (strand-cell (strand-cell nil (domain (long-domain (domain-id 1)))) (domain (long-domain (domain-id 2)))) (domain (long-domain (complement (domain-id 1)))))";
+        let one: &str = "(double-strand-cell (strand-cell nil (domain (long-domain (domain-id 1)))) (strand-cell (domain (long-domain (complement (domain-id 1)))) nil) nil)";
+        assert_eq!(
+            complex_error(strand, &format!("(pairing 0:0 0:1 {})", one)),
+            MetaErrorKind::PairingNotOnStrands(Box::new(Pairing {
+                bottom: Location {
+                    strand: 0,
+                    domain: 0,
+                },
+                top: Location {
+                    strand: 0,
+                    domain: 1,
+                },
+                domains: parse_domains("1").unwrap(),
+            }))
+        );
+        assert_eq!(
+            complex_error(
+                strand,
+                &format!("(pairing 0:0 0:2 {}) (pairing 0:0 0:2 {})", one, one)
+            ),
+            MetaErrorKind::OverlappingPairings
+        );
+        assert_eq!(
+            complex_error(
+                &format!("{} {}", strand, strand),
+                &format!("(pairing 0:0 0:2 {})", one)
+            ),
+            MetaErrorKind::Disconnected
+        );
+        assert_eq!(
+            add_hairpin_to_egraph(&mut egraph, &parse_domains("1").unwrap(), &vec![])
+                .unwrap_err()
+                .kind,
+            MetaErrorKind::EmptyLoop
+        );
+
+        // A double strand over strands which don't bind, and a complex built
+        // on it, both report the double strand.
+        let mut egraph = EGraph::<Language, Meta>::default();
+        let bottom: Id = add_strand_to_egraph(&mut egraph, &parse_domains("1").unwrap()).unwrap();
+        let top: Id = add_strand_to_egraph(&mut egraph, &parse_domains("2").unwrap()).unwrap();
+        let nil: Id = egraph.add(ENode::leaf(Language::Nil));
+        let double_strand: Id = egraph.add(ENode::new(
+            Language::DoubleStrandCell,
+            vec![bottom, top, nil],
+        ));
+        let complex: Id = egraph.add(ENode::new(Language::Complex, vec![bottom, double_strand]));
+        assert_eq!(egraph[double_strand].metadata.value(), None);
+        assert_eq!(
+            egraph[double_strand].metadata.error().unwrap().kind,
+            MetaErrorKind::NotComplementary
+        );
+        assert_eq!(
+            egraph[complex].metadata.error(),
+            egraph[double_strand].metadata.error()
+        );
+        assert!(find_error(&egraph).is_some());
+        assert!(rewrites::run(&mut egraph, &rewrites::strand_cell_associativity()).is_err());
+    }
+
+    #[test]
+    fn strand_instances() {
+        let mut egraph = EGraph::<Language, Meta>::default();
+        let domains: Vec<Domain> = parse_domains("t0^ 1").unwrap();
+        let first: Id = add_strand_instance_to_egraph(&mut egraph, 0, &domains).unwrap();
+        let second: Id = add_strand_instance_to_egraph(&mut egraph, 1, &domains).unwrap();
+
+        // The copies are kept apart, but share their strand cells.
+        assert_ne!(egraph.find(first), egraph.find(second));
+        assert_eq!(
+            egraph[first].metadata.value(),
+            Some(&Value::StrandValue {
+                id: 0,
+                domains: domains.clone()
+            })
+        );
+        assert_eq!(
+            find_strand_instances(&egraph, &domains),
+            vec![(0, egraph.find(first)), (1, egraph.find(second))]
+        );
+        assert_eq!(find_strand_cells(&egraph, &domains).len(), 1);
+        assert_eq!(strand_ids(&egraph, second), vec![1]);
+
+        assert_eq!("#3".parse(), Ok(StrandTag(3)));
+        assert_eq!(StrandTag(3).to_string(), "#3");
+        assert!("3".parse::<StrandTag>().is_err());
+    }
+
+    #[test]
+    fn merge_metadata() {
+        let mut egraph = EGraph::<Language, Meta>::default();
+        let strand: Id = add_strand_to_egraph(&mut egraph, &parse_domains("1").unwrap()).unwrap();
+        let double_strand: Id =
+            add_double_strand_to_egraph(&mut egraph, &parse_domains("1").unwrap()).unwrap();
+        let other_strand: Id =
+            add_strand_to_egraph(&mut egraph, &parse_domains("2").unwrap()).unwrap();
+
+        // A strand joins with a double strand over it, from either side, to
+        // give the double strand.
+        let merged: Meta = egraph[strand]
+            .metadata
+            .merge(&egraph[double_strand].metadata);
+        assert_eq!(
+            merged,
+            egraph[double_strand]
+                .metadata
+                .merge(&egraph[strand].metadata)
+        );
+        assert_eq!(
+            merged.value(),
+            Some(&Value::DoubleStrandCellValue(parse_domains("1").unwrap()))
+        );
+        assert!(merged.conflicts().is_empty());
+        let top_strand: Meta = Meta {
+            value: Some(Value::StrandCellValue(parse_domains("1*").unwrap())),
+            error: None,
+            conflicts: vec![],
+        };
+        assert_eq!(top_strand.merge(&egraph[double_strand].metadata), merged);
+        let id: Id = egraph.union(strand, double_strand);
+        egraph.rebuild();
+        assert_eq!(egraph[id].metadata, merged);
+        assert!(find_conflicts(&egraph).is_empty());
+
+        // A strand which really disagrees is a conflict, which is recorded
+        // rather than panicking, however the merge is ordered.
+        let conflicting: Meta = egraph[other_strand].metadata.merge(&egraph[id].metadata);
+        assert_eq!(
+            conflicting,
+            egraph[id].metadata.merge(&egraph[other_strand].metadata)
+        );
+        assert_eq!(conflicting.conflicts().len(), 1);
+        let id: Id = egraph.union(other_strand, id);
+        egraph.rebuild();
+        assert!(!find_conflicts(&egraph).is_empty());
+        assert_eq!(egraph[id].metadata.conflicts().len(), 1);
+        assert_eq!(
+            egraph[id].metadata.value(),
+            Some(&egraph[id].metadata.conflicts()[0].kept)
+        );
+
+        // An ill-formed eclass keeps its error and has no value.
+        let error: Meta = Meta {
+            value: None,
+            error: Some(MetaError {
+                enode: ENode::leaf(Language::Nil),
+                child_values: vec![],
+                kind: MetaErrorKind::EmptyLoop,
+            }),
+            conflicts: vec![],
+        };
+        let merged: Meta = error.merge(&egraph[id].metadata);
+        assert_eq!(merged, egraph[id].metadata.merge(&error));
+        assert_eq!(merged.value(), None);
+        assert_eq!(merged.error(), error.error());
+    }
+
+    #[test]
+    fn compact_notation() {
+        let domains: Vec<Domain> = parse_domains("t0^ 1 2* 3^*").unwrap();
+        assert_eq!(
+            domains,
+            vec![
+                Domain::toehold(DomainId::DomainId(0)),
+                Domain::long(DomainId::DomainId(1)),
+                Domain::long(DomainId::Complement(Box::new(DomainId::DomainId(2)))),
+                Domain::toehold(DomainId::Complement(Box::new(DomainId::DomainId(3)))),
+            ]
+        );
+        assert_eq!(print_domains(&domains), "t0^ 1 2* t3^*");
+        assert_eq!(parse_domains(&print_domains(&domains)).unwrap(), domains);
+        assert!(parse_domains("t0^ x-y").is_err());
+
+        // Named domains keep their names.
+        let domains: Vec<Domain> = parse_domains("rep_out x^* t1^").unwrap();
+        assert_eq!(
+            domains[0],
+            Domain::long(DomainId::Named(Name::new("rep_out")))
+        );
+        assert_eq!(domains[2], Domain::toehold(DomainId::DomainId(1)));
+        assert_eq!(print_domains(&domains), "rep_out x^* t1^");
+        assert_eq!(domains[1].name(), "x");
+        assert_eq!(domains[2].name(), "t1");
+
+        // A domain named like a numbered one is escaped wherever it's written.
+        let named: Domain = Domain::toehold(DomainId::Named(Name::new("t1")));
+        assert_ne!(named, domains[2]);
+        assert_eq!(named.name(), "t1_");
+        assert_eq!(named.to_string(), "t1_^");
+        assert_eq!(parse_domains("t1_^").unwrap(), vec![named]);
+        assert_eq!(
+            parse_domains("d2").unwrap(),
+            vec![Domain::long(DomainId::DomainId(2))]
+        );
+
+        // Lengths other than the default are written out, in compact
+        // notation and in the egraph.
+        let sized: Vec<Domain> = parse_domains("t2:7^* x:15 t3:6^").unwrap();
+        assert_eq!(
+            sized,
+            vec![
+                Domain::Toehold(DomainId::DomainId(2).complement(), 7),
+                Domain::Long(DomainId::Named(Name::new("x")), 15),
+                Domain::toehold(DomainId::DomainId(3)),
+            ]
+        );
+        assert_eq!(print_domains(&sized), "t2:7^* x:15 t3^");
+        assert!(parse_domains("x:").is_err());
+        let mut egraph = EGraph::<Language, Meta>::default();
+        add_strand_to_egraph(&mut egraph, &sized).unwrap();
+        for (pattern, found) in &[
+            ("(toehold-domain (complement (domain-id 2)) 7nt)", 1),
+            ("(long-domain (domain-id x) 15nt)", 1),
+            ("(toehold-domain (domain-id 3))", 1),
+            ("(toehold-domain (domain-id 3) ?length)", 0),
+        ] {
+            let pattern: egg::Pattern<Language> = pattern.parse().unwrap();
+            assert_eq!(egg::Searcher::search(&pattern, &egraph).len(), *found);
+        }
+        assert_eq!(find_strand_cells(&egraph, &sized).len(), 1);
+        let mut egraph = EGraph::<Language, Meta>::default();
+        add_strand_to_egraph(&mut egraph, &domains).unwrap();
+        let pattern: egg::Pattern<Language> = "(domain-id rep_out)".parse().unwrap();
+        assert_eq!(egg::Searcher::search(&pattern, &egraph).len(), 1);
+
+        let mut egraph = EGraph::<Language, Meta>::default();
+        let id: Id = add_strand_to_egraph(&mut egraph, &parse_domains("t0^ 1").unwrap()).unwrap();
+        assert_eq!(
+            egraph[id].metadata.value,
+            Some(Value::StrandCellValue(parse_domains("t0^ 1").unwrap()))
+        );
+    }
 }
@@ -0,0 +1,580 @@
+//! Reading and writing the Pepper Intermediate Language (PIL), as used by
+//! Peppercorn and Nuskell. We understand:
+//!
+//! ```text
+//! # Comments.
+//! length a = 6
+//! sequence a = NNNNNN          (ignored; see sequence design)
+//! strand s = a b*
+//! structure S = s + s : ((+))
+//! S = a( b* + ) @initial 10 nM (a kernel string; anything after @ is ignored)
+//! ```
+//!
+//! Domains named `t<n>` or `d<n>` (as written by write_pil()) are toehold or
+//! long domain `n`. Other names are read as in symbol::Symbol (or, if they
+//! can't be symbols, given fresh numbers), and are toeholds if they're
+//! declared to be at most MAX_TOEHOLD_LENGTH long.
+
+use super::crn::species_name;
+use super::dsd::ParseError;
+use super::structure::{kernel, strands};
+use super::*;
+use std::collections::HashMap;
+
+/// Domains with other names which are at most this long are toeholds.
+pub const MAX_TOEHOLD_LENGTH: usize = 9;
+
+/// What read_pil() added to the egraph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PilSystem {
+    /// The domain each domain name stands for, with its kind and length.
+    pub domains: HashMap<String, Domain>,
+    /// Each species, by name, as a complex.
+    pub species: Vec<(String, Id)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    text: String,
+    column: usize,
+}
+
+fn tokenize(line: &str) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::default();
+    let mut current: Option<Token> = None;
+    for (i, c) in line.chars().enumerate() {
+        if c.is_alphanumeric() || c == '_' || c == '-' || c == '.' {
+            current
+                .get_or_insert(Token {
+                    text: String::default(),
+                    column: i + 1,
+                })
+                .text
+                .push(c);
+            continue;
+        }
+        tokens.extend(current.take());
+        if !c.is_whitespace() {
+            tokens.push(Token {
+                text: c.to_string(),
+                column: i + 1,
+            });
+        }
+    }
+    tokens.extend(current.take());
+    tokens
+}
+
+/// A domain as written: its name and whether it's complemented.
+type DomainName = (String, bool);
+
+/// A structure as written, before domain names are resolved: strands, and the
+/// pairs of (strand, domain) positions which bind.
+type WrittenStructure = (Vec<Vec<DomainName>>, Vec<((usize, usize), (usize, usize))>);
+
+/// Reads `source` and adds each species it defines to `egraph`.
+pub fn read_pil(
+    egraph: &mut EGraph<Language, Meta>,
+    source: &str,
+) -> Result<PilSystem, ParseError> {
+    let mut lengths: HashMap<String, usize> = HashMap::default();
+    let mut strands: HashMap<String, Vec<DomainName>> = HashMap::default();
+    // Each structure, along with the line it's on.
+    let mut structures: Vec<(String, WrittenStructure, usize)> = Vec::default();
+
+    for (line_index, line) in source.lines().enumerate() {
+        let line_number: usize = line_index + 1;
+        let line: &str = line.split('#').next().unwrap();
+        let tokens: Vec<Token> = tokenize(line);
+        let error = |column: usize, message: String| ParseError {
+            line: line_number,
+            column: column,
+            message: message,
+        };
+        let end_column: usize = line.chars().count() + 1;
+        // The position of the '=' in a statement, and what comes after it.
+        let definition = |keyword_length: usize| -> Result<(String, &[Token]), ParseError> {
+            let name: &Token = tokens
+                .get(keyword_length)
+                .ok_or_else(|| error(end_column, "Expected a name".to_string()))?;
+            match tokens.get(keyword_length + 1) {
+                Some(token) if token.text == "=" => {
+                    Ok((name.text.clone(), &tokens[keyword_length + 2..]))
+                }
+                Some(token) => Err(error(
+                    token.column,
+                    format!("Expected '=', found '{}'", token.text),
+                )),
+                None => Err(error(end_column, "Expected '='".to_string())),
+            }
+        };
+
+        match tokens.first().map(|token: &Token| token.text.as_str()) {
+            None => continue,
+            Some("sequence") => continue,
+            Some("length") => {
+                let (name, rest) = definition(1)?;
+                let length: usize = match rest {
+                    [token] => token.text.parse().map_err(|_| {
+                        error(
+                            token.column,
+                            format!("Expected a length, found '{}'", token.text),
+                        )
+                    })?,
+                    _ => return Err(error(end_column, "Expected a length".to_string())),
+                };
+                lengths.insert(name, length);
+            }
+            Some("strand") => {
+                let (name, rest) = definition(1)?;
+                // Drop any trailing ": <length>".
+                let rest: &[Token] = match rest.iter().position(|token| token.text == ":") {
+                    Some(i) => &rest[..i],
+                    None => rest,
+                };
+                let (written, _) = parse_kernel(rest, end_column, &error)?;
+                strands.insert(name, written.into_iter().flatten().collect());
+            }
+            Some("structure") => {
+                // An optional concentration in brackets comes before the name.
+                let keyword_length: usize = match tokens.get(1) {
+                    Some(token) if token.text == "[" => {
+                        1 + tokens
+                            .iter()
+                            .position(|token| token.text == "]")
+                            .ok_or_else(|| error(token.column, "Expected ']'".to_string()))?
+                    }
+                    _ => 1,
+                };
+                let (name, rest) = definition(keyword_length)?;
+                structures.push((
+                    name,
+                    parse_structure(rest, &strands, end_column, &error)?,
+                    line_number,
+                ));
+            }
+            Some(_) => {
+                let (name, rest) = definition(0)?;
+                let rest: &[Token] = match rest.iter().position(|token| token.text == "@") {
+                    Some(i) => &rest[..i],
+                    None => rest,
+                };
+                structures.push((name, parse_kernel(rest, end_column, &error)?, line_number));
+            }
+        }
+    }
+
+    // Resolve domain names to ids, keeping the ids of t<n> and d<n>.
+    let numbered = |name: &str| -> Option<(bool, DomainIdValue)> {
+        let is_toehold: bool = name.starts_with('t');
+        if !is_toehold && !name.starts_with('d') {
+            return None;
+        }
+        match name.parse::<Symbol>() {
+            Ok(Symbol::Number(id)) => Some((is_toehold, id)),
+            _ => None,
+        }
+    };
+    let mut names: Vec<String> = lengths.keys().cloned().collect();
+    for (_, (written, _), _) in structures.iter() {
+        names.extend(written.iter().flatten().map(|(name, _)| name.clone()));
+    }
+    names.sort();
+    names.dedup();
+    let mut domain_ids: HashMap<String, DomainId> = HashMap::default();
+    let mut is_toehold: HashMap<String, bool> = HashMap::default();
+    for name in names.iter() {
+        if let Some((toehold, id)) = numbered(name) {
+            domain_ids.insert(name.clone(), DomainId::DomainId(id));
+            is_toehold.insert(name.clone(), toehold);
+        }
+    }
+    let mut next_id: DomainIdValue = 0;
+    for name in names.iter() {
+        if domain_ids.contains_key(name) {
+            continue;
+        }
+        let id: DomainId = match name.parse::<Symbol>() {
+            Ok(Symbol::Name(name)) => DomainId::Named(name),
+            _ => {
+                while domain_ids
+                    .values()
+                    .any(|id: &DomainId| *id == DomainId::DomainId(next_id))
+                {
+                    next_id += 1;
+                }
+                DomainId::DomainId(next_id)
+            }
+        };
+        domain_ids.insert(name.clone(), id);
+        is_toehold.insert(
+            name.clone(),
+            lengths
+                .get(name)
+                .map_or(false, |length: &usize| *length <= MAX_TOEHOLD_LENGTH),
+        );
+    }
+
+    let domains: HashMap<String, Domain> = domain_ids
+        .into_iter()
+        .map(|(name, domain_id): (String, DomainId)| {
+            let domain: Domain = if is_toehold[&name] {
+                Domain::toehold(domain_id)
+            } else {
+                Domain::long(domain_id)
+            };
+            let domain: Domain = match lengths.get(&name) {
+                Some(length) => domain.with_length(*length),
+                None => domain,
+            };
+            (name, domain)
+        })
+        .collect();
+    let resolve = |(name, is_complement): &DomainName| -> Domain {
+        if *is_complement {
+            domains[name].complement()
+        } else {
+            domains[name].clone()
+        }
+    };
+    let mut species: Vec<(String, Id)> = Vec::default();
+    for (name, (written, pairs), line_number) in structures.iter() {
+        let strands: Vec<Vec<Domain>> = written
+            .iter()
+            .map(|strand: &Vec<DomainName>| strand.iter().map(resolve).collect())
+            .collect();
+        let id: Id =
+            add_structure_to_egraph(egraph, &strands, pairs).map_err(|error: MetaError| {
+                ParseError {
+                    line: *line_number,
+                    column: 1,
+                    message: error.to_string(),
+                }
+            })?;
+        species.push((name.clone(), id));
+    }
+    egraph.rebuild();
+
+    Ok(PilSystem {
+        domains: domains,
+        species: species,
+    })
+}
+
+/// kernel: [name ['*'] ['('] | ')' | '+']*
+fn parse_kernel(
+    tokens: &[Token],
+    end_column: usize,
+    error: &dyn Fn(usize, String) -> ParseError,
+) -> Result<WrittenStructure, ParseError> {
+    let mut strands: Vec<Vec<DomainName>> = vec![Vec::default()];
+    let mut pairs: Vec<((usize, usize), (usize, usize))> = Vec::default();
+    let mut open: Vec<((usize, usize), DomainName, usize)> = Vec::default();
+    let mut i: usize = 0;
+    while i < tokens.len() {
+        let token: &Token = &tokens[i];
+        match token.text.as_str() {
+            "+" => strands.push(Vec::default()),
+            ")" => {
+                let (opening, (name, is_complement), _) = open
+                    .pop()
+                    .ok_or_else(|| error(token.column, "Unmatched ')'".to_string()))?;
+                let strand: usize = strands.len() - 1;
+                pairs.push((opening, (strand, strands[strand].len())));
+                strands[strand].push((name, !is_complement));
+            }
+            text if text
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.') =>
+            {
+                let is_complement: bool = tokens.get(i + 1).map_or(false, |t| t.text == "*");
+                if is_complement {
+                    i += 1;
+                }
+                let domain: DomainName = (text.to_string(), is_complement);
+                let strand: usize = strands.len() - 1;
+                let position: (usize, usize) = (strand, strands[strand].len());
+                if tokens.get(i + 1).map_or(false, |t| t.text == "(") {
+                    i += 1;
+                    open.push((position, domain.clone(), token.column));
+                }
+                strands[strand].push(domain);
+            }
+            text => {
+                return Err(error(token.column, format!("Unexpected '{}'", text)));
+            }
+        }
+        i += 1;
+    }
+    if let Some((_, _, column)) = open.first() {
+        return Err(error(*column, "Unmatched '('".to_string()));
+    }
+    if strands
+        .iter()
+        .any(|strand: &Vec<DomainName>| strand.is_empty())
+    {
+        return Err(error(end_column, "Expected domains".to_string()));
+    }
+    Ok((strands, pairs))
+}
+
+/// structure: strand-name ['+' strand-name]* ':' dot-paren-plus
+fn parse_structure(
+    tokens: &[Token],
+    strands: &HashMap<String, Vec<DomainName>>,
+    end_column: usize,
+    error: &dyn Fn(usize, String) -> ParseError,
+) -> Result<WrittenStructure, ParseError> {
+    let colon: usize = tokens
+        .iter()
+        .position(|token: &Token| token.text == ":")
+        .ok_or_else(|| error(end_column, "Expected ':'".to_string()))?;
+    let written: Vec<Vec<DomainName>> = tokens[..colon]
+        .iter()
+        .filter(|token: &&Token| token.text != "+")
+        .map(|token: &Token| {
+            strands
+                .get(&token.text)
+                .cloned()
+                .ok_or_else(|| error(token.column, format!("Unknown strand '{}'", token.text)))
+        })
+        .collect::<Result<_, _>>()?;
+
+    // Dot-paren-plus: one character per domain.
+    let mut pairs: Vec<((usize, usize), (usize, usize))> = Vec::default();
+    let mut open: Vec<((usize, usize), usize)> = Vec::default();
+    let (mut strand, mut domain): (usize, usize) = (0, 0);
+    for token in tokens[colon + 1..].iter() {
+        for (offset, c) in token.text.chars().enumerate() {
+            let column: usize = token.column + offset;
+            if c == '+' {
+                strand += 1;
+                domain = 0;
+                continue;
+            }
+            if strand >= written.len() || domain >= written[strand].len() {
+                return Err(error(
+                    column,
+                    "Structure is longer than its strands".to_string(),
+                ));
+            }
+            match c {
+                '(' => open.push(((strand, domain), column)),
+                ')' => {
+                    let (opening, _) = open
+                        .pop()
+                        .ok_or_else(|| error(column, "Unmatched ')'".to_string()))?;
+                    pairs.push((opening, (strand, domain)));
+                }
+                '.' => (),
+                _ => return Err(error(column, format!("Unexpected '{}'", c))),
+            }
+            domain += 1;
+        }
+    }
+    if let Some((_, column)) = open.first() {
+        return Err(error(*column, "Unmatched '('".to_string()));
+    }
+    Ok((written, pairs))
+}
+
+/// Adds a species as a complex, with a pairing for each helix (run of
+/// consecutive pairs).
+fn add_structure_to_egraph(
+    egraph: &mut EGraph<Language, Meta>,
+    strands: &[Vec<Domain>],
+    pairs: &[((usize, usize), (usize, usize))],
+) -> Result<Id, MetaError> {
+    let location = |(strand, domain): (usize, usize)| Location {
+        strand: strand,
+        domain: domain,
+    };
+    let pairs: Vec<(Location, Location)> = pairs
+        .iter()
+        .map(|(bottom, top): &((usize, usize), (usize, usize))| (location(*bottom), location(*top)))
+        .collect();
+    let pairings: Vec<Pairing> = pairings_from_pairs(strands, &pairs);
+
+    add_complex_to_egraph(egraph, &strands.to_vec(), &pairings)
+}
+
+/// Writes `species` (e.g. found with enumerate::find_species()) out as PIL:
+/// the length of every domain they use, then each species as a kernel string,
+/// named as in the CRN export.
+pub fn write_pil(egraph: &EGraph<Language, Meta>, species: &[Id]) -> String {
+    fn base_domain(domain: &Domain) -> Domain {
+        match domain {
+            Domain::Toehold(domain_id, length) => Domain::Toehold(domain_id.base(), *length),
+            Domain::Long(domain_id, length) => Domain::Long(domain_id.base(), *length),
+        }
+    }
+
+    let kernels: Vec<(String, String)> = species
+        .iter()
+        .filter_map(|id: &Id| {
+            let value: &Value = egraph[*id].metadata.value.as_ref()?;
+            Some((species_name(value), kernel(egraph, *id)?))
+        })
+        .collect();
+
+    // Every domain on the species' strands.
+    let mut domains: Vec<Domain> = species
+        .iter()
+        .filter_map(|id: &Id| strands(egraph, *id))
+        .flatten()
+        .flatten()
+        .map(|domain: Domain| base_domain(&domain))
+        .collect();
+    domains.sort();
+    domains.dedup();
+
+    let mut out = String::default();
+    out.push_str("# Domains\n");
+    for domain in domains.iter() {
+        out.push_str(&format!("length {} = {}\n", domain.name(), domain.length()));
+    }
+    out.push_str("\n# Species\n");
+    for (name, kernel) in kernels {
+        out.push_str(&format!("{} = {}\n", name, kernel));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_and_write() {
+        let mut egraph = EGraph::<Language, Meta>::default();
+        let system: PilSystem = read_pil(
+            &mut egraph,
+            "# A gate and its input.
+             length t = 6
+             length x = 15
+             length y = 15
+             sequence t = NNNNNN
+             input = t x @initial 10 nM
+             strand top = x y
+             strand bottom = x* t*
+             structure [1 nM] gate = top + bottom : (.+).
+             gate2 = x( y + ) t*
+             structure gate3 = bottom + top : (.+).
+             length t1 = 7
+             length d2 = 15
+             loop = d2( t1 t1 d2 )",
+        )
+        .unwrap();
+
+        let t: Domain = system.domains["t"].clone();
+        let x: Domain = system.domains["x"].clone();
+        let y: Domain = system.domains["y"].clone();
+        assert_eq!(x.length(), 15);
+        assert_eq!(t.length(), 6);
+        assert_eq!(
+            system.domains["t1"],
+            Domain::Toehold(DomainId::DomainId(1), 7)
+        );
+        assert_eq!(
+            system.domains["d2"],
+            Domain::Long(DomainId::DomainId(2), 15)
+        );
+        assert_eq!(system.species.len(), 5);
+        assert_eq!(
+            egraph[system.species[0].1].metadata.value,
+            Some(Value::ComplexValue {
+                strands: vec![vec![t.clone(), x.clone()]],
+                pairings: vec![],
+            })
+        );
+        let gate_value = Some(Value::ComplexValue {
+            strands: vec![
+                vec![x.complement(), t.complement()],
+                vec![x.clone(), y.clone()],
+            ],
+            pairings: vec![Pairing {
+                bottom: Location {
+                    strand: 0,
+                    domain: 0,
+                },
+                top: Location {
+                    strand: 1,
+                    domain: 0,
+                },
+                domains: vec![x.complement()],
+            }],
+        });
+        assert_eq!(egraph[system.species[1].1].metadata.value, gate_value);
+        assert_eq!(egraph[system.species[2].1].metadata.value, gate_value);
+        // The same complex, with its strands listed the other way round.
+        assert_eq!(
+            egraph.find(system.species[3].1),
+            egraph.find(system.species[1].1)
+        );
+
+        // Writing and reading back gives the same species.
+        let ids: Vec<Id> = system.species.iter().map(|(_, id)| *id).collect();
+        let written: String = write_pil(&egraph, &ids);
+        let mut other_egraph = EGraph::<Language, Meta>::default();
+        let reread: PilSystem = read_pil(&mut other_egraph, &written).unwrap();
+        assert_eq!(reread.species.len(), 5);
+        // Lengths come from the declarations, even for domains only in loops.
+        assert!(written.contains("length t1 = 7\n"));
+        assert!(written.contains("length d2 = 15\n"));
+        for ((_, id), (_, reread_id)) in system.species.iter().zip(reread.species.iter()) {
+            assert_eq!(
+                egraph[*id].metadata.value,
+                other_egraph[*reread_id].metadata.value
+            );
+        }
+    }
+
+    #[test]
+    fn read_errors() {
+        let mut egraph = EGraph::<Language, Meta>::default();
+        assert_eq!(
+            read_pil(&mut egraph, "length a = 6\ns = a( a"),
+            Err(ParseError {
+                line: 2,
+                column: 5,
+                message: "Unmatched '('".to_string(),
+            })
+        );
+        assert_eq!(
+            read_pil(&mut egraph, "structure s = nope : .")
+                .unwrap_err()
+                .column,
+            15
+        );
+    }
+
+    #[test]
+    fn write_enumerated_species() {
+        use super::super::enumerate::{enumerate_in, find_species, species};
+        use super::super::rewrites::*;
+
+        let mut egraph = EGraph::<Language, Meta>::default();
+        add_strand_to_egraph(&mut egraph, &parse_domains("t0^ 1").unwrap()).unwrap();
+        add_strand_to_egraph(&mut egraph, &parse_domains("1* t0^*").unwrap()).unwrap();
+        let mut structural_rules = Vec::default();
+        structural_rules.extend(strand_cell_associativity());
+        structural_rules.extend(strand_cell_nil_commutativity());
+        let reactions = enumerate_in(
+            &mut egraph,
+            &structural_rules,
+            &[toehold_bind(), bind()],
+            None,
+        )
+        .unwrap();
+
+        let ids: Vec<Id> = species(&reactions)
+            .iter()
+            .map(|value: &Value| find_species(&egraph, value).unwrap())
+            .collect();
+        let written: String = write_pil(&egraph, &ids);
+        assert!(written.contains("length t0 = 6\nlength d1 = 20\n"));
+        assert!(written.contains("t0_d1 = t0 d1\n"));
+        assert!(written.contains("complex_2_t0__d1_d1__0__t0__0_p0_0_1_1_1 = t0( d1 + d1* )\n"));
+        assert!(written.contains("complex_2_t0__d1_d1__0__t0__0_p0_0_1_0_2 = t0( d1( + ) )\n"));
+    }
+}
@@ -1,6 +1,7 @@
 use super::*;
-use egg::{rewrite, Applier, Pattern, Rewrite, SearchMatches, Searcher, Subst, Var};
+use egg::{rewrite, Applier, Rewrite, SearchMatches, Searcher, Subst, Var};
 use log::{debug, info, trace};
+use std::rc::Rc;
 
 pub fn strand_cell_associativity() -> Vec<Rewrite<Language, Meta>> {
     vec![
@@ -87,340 +88,917 @@ pub fn simplify_double_complement() -> Rewrite<Language, Meta> {
             "?a")
 }
 
-/// Binds toeholds, and then binds everything after the toehold that can be
-/// bound.
-pub fn toehold_bind() -> Rewrite<Language, Meta> {
-    const A: &'static str = "?A";
-    const B: &'static str = "?B";
-    let a_var: Var = A.parse().unwrap();
-    let b_var: Var = B.parse().unwrap();
-    struct ToeholdSearcher {
-        a_var: Var,
-        b_var: Var,
-    };
-    impl ToeholdSearcher {
-        fn get_complement_match(
-            &self,
-            domain_id: DomainIdValue,
-            egraph: &EGraph<Language, Meta>,
-        ) -> Option<Id> {
-            let complement_pattern: Pattern<Language> = format!(
-                "(strand-cell (domain (toehold-domain (complement (domain-id {})))) nil)",
-                domain_id
-            )
-            .parse()
-            .unwrap();
-            let found: Vec<SearchMatches> = complement_pattern.search(egraph);
-            match found.len() {
-                0 => None,
-                1 => Some(found[0].eclass),
-                _ => panic!("How did we find more than one eclass for this pattern?"),
+/// A species, as reaction rules see it: the member strands of a complex, and
+/// which of their domains are bound to which. Each member is kept as the
+/// eclass the complex lists it as (a strand cell, or a strand instance), so
+/// that every product can be traced back to the strand instances it's made
+/// from with strand_ids().
+#[derive(Debug, Clone, PartialEq)]
+struct Species {
+    members: Vec<Id>,
+    strands: Vec<Vec<Domain>>,
+    /// Each pair of bound domains, once.
+    pairs: Vec<(Location, Location)>,
+}
+
+impl Species {
+    /// Reads the species held by `eclass`, if it's a well-formed complex.
+    fn find(egraph: &EGraph<Language, Meta>, eclass: Id) -> Option<Species> {
+        match egraph[eclass].metadata.value.as_ref() {
+            Some(Value::ComplexValue { .. }) => (),
+            _ => return None,
+        }
+        let enode = egraph[eclass]
+            .nodes
+            .iter()
+            .find(|enode| enode.op == Language::Complex)?;
+
+        let mut species = Species {
+            members: Vec::default(),
+            strands: Vec::default(),
+            pairs: Vec::default(),
+        };
+        for child in enode.children.iter() {
+            match egraph[*child].metadata.value.as_ref() {
+                Some(Value::StrandCellValue(domains))
+                | Some(Value::StrandValue { domains, .. }) => {
+                    species.members.push(egraph.find(*child));
+                    species.strands.push(domains.clone());
+                }
+                Some(Value::PairingValue(pairing)) => species.pairs.extend(pairing.domain_pairs()),
+                _ => return None,
             }
         }
+        Some(species)
     }
-    impl Searcher<Language, Meta> for ToeholdSearcher {
-        fn search_eclass(
-            &self,
-            egraph: &EGraph<Language, Meta>,
-            eclass: Id,
-        ) -> Option<SearchMatches> {
-            // TODO(gus) should find everything that
-            // "(strand-cell nil (domain (toehold-domain (domain-id ?domain-id))))"
-            // would find.
-            let pattern: Pattern<Language> =
-                "(strand-cell (domain (toehold-domain (domain-id ?domain-id))) nil)"
-                    .parse()
-                    .unwrap();
-
-            match pattern.search_eclass(egraph, eclass) {
-                None => None,
-                Some(search_matches) => {
-                    let domain_id: &DomainId = match egraph
-                        [search_matches.substs[0][&"?domain-id".parse().unwrap()]]
-                        .metadata
-                        .value
-                        .as_ref()
-                        .unwrap()
-                    {
-                        Value::DomainIdValue(v) => v,
-                        _ => panic!(),
-                    };
-                    let domain_id_value = match domain_id {
-                        DomainId::DomainId(v) => v,
-                        // We should have filtered this out, given the
-                        // pattern we're searching for.
-                        DomainId::Complement(_) => panic!(),
-                    };
 
-                    for subst in search_matches.substs[1..].iter() {
-                        assert_eq!(
-                            match egraph[subst[&"?domain-id".parse().unwrap()]]
-                                .metadata
-                                .value
-                                .as_ref()
-                                .unwrap()
-                            {
-                                Value::DomainIdValue(v) => match v {
-                                    DomainId::DomainId(v) => v,
-                                    DomainId::Complement(_) => panic!(),
-                                },
-                                _ => panic!(),
-                            },
-                            domain_id_value
-                        );
-                    }
+    /// Every location in the species, in order.
+    fn locations(&self) -> Vec<Location> {
+        self.strands
+            .iter()
+            .enumerate()
+            .flat_map(|(strand, domains): (usize, &Vec<Domain>)| {
+                (0..domains.len()).map(move |domain: usize| Location {
+                    strand: strand,
+                    domain: domain,
+                })
+            })
+            .collect()
+    }
 
-                    match self.get_complement_match(*domain_id_value, egraph) {
-                        None => None,
-                        Some(id) => Some(SearchMatches {
-                            eclass: eclass,
-                            substs: {
-                                let mut new_subst = Subst::default();
-                                new_subst.insert(self.a_var.clone(), eclass);
-                                new_subst.insert(self.b_var.clone(), id);
-                                vec![new_subst]
-                            },
-                        }),
-                    }
+    fn domain(&self, location: Location) -> Option<&Domain> {
+        self.strands.get(location.strand)?.get(location.domain)
+    }
+
+    /// The location `offset` domains along from `location`, on the same
+    /// strand, if there is one.
+    fn step(&self, location: Location, offset: isize) -> Option<Location> {
+        let domain: usize = if offset < 0 {
+            location.domain.checked_sub(offset.unsigned_abs())?
+        } else {
+            location.domain + offset as usize
+        };
+        self.domain(Location {
+            strand: location.strand,
+            domain: domain,
+        })?;
+        Some(Location {
+            strand: location.strand,
+            domain: domain,
+        })
+    }
+
+    fn partner(&self, location: Location) -> Option<Location> {
+        self.pairs.iter().find_map(|(a, b): &(Location, Location)| {
+            if *a == location {
+                Some(*b)
+            } else if *b == location {
+                Some(*a)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Whether pairing `a` with `b` would stack onto a helix already next to
+    /// them, i.e. continue it.
+    fn stacks(&self, a: Location, b: Location) -> bool {
+        [1, -1].iter().any(
+            |offset: &isize| match (self.step(a, *offset), self.step(b, -offset)) {
+                (Some(next_a), Some(next_b)) => self.partner(next_a) == Some(next_b),
+                _ => false,
+            },
+        )
+    }
+
+    /// Where the branch migration domains of an invader whose toehold
+    /// complement is at `toehold_complement` end (they run backwards from
+    /// there), if they're not directly next to it, for binding to a template
+    /// whose toehold is at `toehold`; one for each layout they can be found
+    /// in. A remote toehold's spacer runs from the toehold complement up to the
+    /// first domain complementary to the template's first branch migration
+    /// domain, and must be unbound; if that domain is right next to the toehold
+    /// complement, the toehold isn't remote. An associative toehold's strand
+    /// must be held, right next to the toehold complement, by a bridge to a
+    /// third strand, the invader.
+    fn spaced_toeholds(
+        &self,
+        toehold: Location,
+        toehold_complement: Location,
+    ) -> Vec<(ToeholdLayout, Location)> {
+        let mut found: Vec<(ToeholdLayout, Location)> = Vec::default();
+        let branch_migration_complement: Option<Domain> = self
+            .step(toehold, 1)
+            .and_then(|template: Location| self.domain(template))
+            .map(Domain::complement);
+        let mut spacer_domains: usize = 0;
+        let mut next: Option<Location> = self.step(toehold_complement, -1);
+        while let Some(candidate) = next {
+            if self.domain(candidate) == branch_migration_complement.as_ref() {
+                if spacer_domains > 0 {
+                    found.push((ToeholdLayout::Remote(spacer_domains), candidate));
                 }
+                break;
+            }
+            if self.partner(candidate).is_some() {
+                break;
             }
+            spacer_domains += 1;
+            next = self.step(candidate, -1);
         }
+
+        let bridge_end: Option<Location> = self
+            .step(toehold_complement, -1)
+            .and_then(|bridge: Location| self.partner(bridge));
+        if let Some(bridge_end) = bridge_end {
+            if bridge_end.strand != toehold.strand && bridge_end.strand != toehold_complement.strand
+            {
+                if let Some(end) = self.step(bridge_end, -1) {
+                    found.push((ToeholdLayout::Associative, end));
+                }
+            }
+        }
+        found
     }
 
-    struct ToeholdApplier {
-        a_var: Var,
-        b_var: Var,
-    };
-    impl Applier<Language, Meta> for ToeholdApplier {
-        fn apply_one(
-            &self,
-            egraph: &mut EGraph<Language, Meta>,
-            _matched_id: Id,
-            subst: &Subst,
-        ) -> Vec<Id> {
-            let strand_cell: Id = subst[&self.a_var];
-            let strand_complement_cell: Id = subst[&self.b_var];
-            let nil_eclass_id: Id = egraph.add(ENode::leaf(Language::Nil));
-            egraph.add(ENode::new(
-                Language::DoubleStrandCell,
-                vec![strand_cell, strand_complement_cell, nil_eclass_id],
-            ));
-            vec![]
+    /// Every pair of bound domains, read both ways round.
+    fn directed_pairs(&self) -> Vec<(Location, Location)> {
+        self.pairs
+            .iter()
+            .flat_map(|(a, b): &(Location, Location)| vec![(*a, *b), (*b, *a)])
+            .collect()
+    }
+
+    /// Whether the domains at `a` and `b` can bind each other: they're
+    /// complementary and unbound, and if they're on the same strand, there's
+    /// room for a loop between them.
+    fn can_pair(&self, a: Location, b: Location) -> bool {
+        match (self.domain(a), self.domain(b)) {
+            (Some(a_domain), Some(b_domain)) => {
+                *b_domain == a_domain.complement()
+                    && self.partner(a).is_none()
+                    && self.partner(b).is_none()
+                    && (a.strand != b.strand
+                        || std::cmp::max(a.domain, b.domain) - std::cmp::min(a.domain, b.domain)
+                            > 1)
+            }
+            _ => false,
         }
     }
 
-    rewrite!("toehold-bind";
-             {
-                 ToeholdSearcher{
-                     a_var: a_var.clone(),
-                     b_var: b_var.clone(),
-                 }
-             } => {
-                 ToeholdApplier{
-                     a_var: a_var.clone(),
-                     b_var:b_var.clone(),
-                 }
-             }
-    )
-}
+    /// The species with the pairs in `removed` unbound, and those in `added`
+    /// bound.
+    fn rebind(&self, removed: &[(Location, Location)], added: &[(Location, Location)]) -> Species {
+        Species {
+            members: self.members.clone(),
+            strands: self.strands.clone(),
+            pairs: self
+                .pairs
+                .iter()
+                .filter(|(a, b): &&(Location, Location)| {
+                    !removed.contains(&(*a, *b)) && !removed.contains(&(*b, *a))
+                })
+                .chain(added.iter())
+                .cloned()
+                .collect(),
+        }
+    }
 
-/// Rewrite which binds complementary domains, if their adjacent domains are
-/// already bound.
-pub fn bind() -> Rewrite<Language, Meta> {
-    struct BindSearcher {
-        previous_double_strand_cell: Var,
-        next_bottom_strand_cell: Var,
-        next_top_strand_cell: Var,
-    };
-    impl Searcher<Language, Meta> for BindSearcher {
-        fn search_eclass(
-            &self,
-            egraph: &EGraph<Language, Meta>,
-            eclass: Id,
-        ) -> Option<SearchMatches> {
-            let search_pattern: Pattern<Language> = "(double-strand-cell
-                                                      ?a ?b ?c)"
-                .parse()
-                .unwrap();
-            let matches: SearchMatches = match search_pattern.search_eclass(egraph, eclass) {
-                None => return None,
-                Some(m) => m,
-            };
+    /// The pairings of the species, one per helix.
+    fn pairings(&self) -> Vec<Pairing> {
+        pairings_from_pairs(&self.strands, &self.pairs)
+    }
 
-            // If there are two, one must be nil.
-            let (this_bottom_cell_id, this_top_cell_id): (Id, Id) = match matches.substs.as_slice()
-            {
-                [subst] | [subst, _] => {
-                    match egraph[subst[&"?a".parse().unwrap()]]
-                        .metadata
-                        .value
-                        .as_ref()
-                        .unwrap()
-                    {
-                        Value::StrandCellValue(_) => {
-                            (subst[&"?a".parse().unwrap()], subst[&"?b".parse().unwrap()])
-                        }
-                        _ => (subst[&"?b".parse().unwrap()], subst[&"?c".parse().unwrap()]),
-                    }
+    /// `self` and `other` side by side, as a single species which isn't (yet)
+    /// held together.
+    fn join(&self, other: &Species) -> Species {
+        let shift = |location: Location| Location {
+            strand: location.strand + self.strands.len(),
+            domain: location.domain,
+        };
+        Species {
+            members: self
+                .members
+                .iter()
+                .chain(other.members.iter())
+                .cloned()
+                .collect(),
+            strands: self
+                .strands
+                .iter()
+                .chain(other.strands.iter())
+                .cloned()
+                .collect(),
+            pairs: self
+                .pairs
+                .iter()
+                .cloned()
+                .chain(
+                    other
+                        .pairs
+                        .iter()
+                        .map(|(a, b): &(Location, Location)| (shift(*a), shift(*b))),
+                )
+                .collect(),
+        }
+    }
+
+    /// Splits the species into the pieces which are held together, each a
+    /// species of its own, in the order of their first strands.
+    fn components(&self) -> Vec<Species> {
+        // Which strands are bound together, as the lowest strand each is
+        // bound to.
+        let mut component: Vec<usize> = (0..self.strands.len()).collect();
+        for (a, b) in self.pairs.iter() {
+            let (from, to): (usize, usize) = (component[a.strand], component[b.strand]);
+            for c in component.iter_mut() {
+                if *c == std::cmp::max(from, to) {
+                    *c = std::cmp::min(from, to);
                 }
-                _ => panic!(),
-            };
+            }
+        }
 
-            let next_bottom_pattern: Pattern<Language> =
-                "(strand-cell ?this-bottom-cell (domain ?domain))"
-                    .parse()
-                    .unwrap();
-            let next_top_pattern: Pattern<Language> =
-                "(strand-cell (domain ?domain) ?this-top-cell)"
-                    .parse()
-                    .unwrap();
-
-            use itertools::Itertools;
-            let substs_out: Vec<Subst> = next_bottom_pattern
-                .search(egraph)
-                .iter()
-                .cartesian_product(&next_top_pattern.search(egraph))
-                .filter_map(
-                    |(bottom_matches, top_matches): (&SearchMatches, &SearchMatches)| {
-                        // This is only true because ?domain can only have one value
-                        // (will point to a long-domain or a toehold-domain) and
-                        // because ?this-bottom-cell/?this-top-cell can likewise
-                        // only have one value each.
-                        let bottom_subst: &Subst = match bottom_matches.substs.as_slice() {
-                            [subst] => subst,
-                            _ => panic!(),
-                        };
-                        let top_subst: &Subst = match top_matches.substs.as_slice() {
-                            [subst] => subst,
-                            _ => panic!(),
-                        };
-
-                        // First, check that the bottom cell is the same one as
-                        // the one in our double cell.
-                        if bottom_subst[&"?this-bottom-cell".parse().unwrap()]
-                            != this_bottom_cell_id
-                        {
-                            return None;
-                        }
+        let mut labels: Vec<usize> = component.clone();
+        labels.sort();
+        labels.dedup();
+        labels
+            .iter()
+            .map(|label: &usize| {
+                let strands: Vec<usize> = (0..self.strands.len())
+                    .filter(|strand: &usize| component[*strand] == *label)
+                    .collect();
+                let relocate = |location: Location| Location {
+                    strand: strands
+                        .iter()
+                        .position(|strand: &usize| *strand == location.strand)
+                        .unwrap_or(location.strand),
+                    domain: location.domain,
+                };
+                Species {
+                    members: strands.iter().map(|i: &usize| self.members[*i]).collect(),
+                    strands: strands
+                        .iter()
+                        .map(|i: &usize| self.strands[*i].clone())
+                        .collect(),
+                    pairs: self
+                        .pairs
+                        .iter()
+                        .filter(|(a, _): &&(Location, Location)| component[a.strand] == *label)
+                        .map(|(a, b): &(Location, Location)| (relocate(*a), relocate(*b)))
+                        .collect(),
+                }
+            })
+            .collect()
+    }
 
-                        // Then, check that the top cell is also the one in our
-                        // double cell.
-                        if top_subst[&"?this-top-cell".parse().unwrap()] != this_top_cell_id {
-                            return None;
-                        }
+    /// Whether the species can be added as a complex: see canonical_complex().
+    fn is_well_formed(&self) -> bool {
+        canonical_complex(&self.strands, &self.pairings()).is_ok()
+    }
 
-                        // Finally, check that the domains are complementary.
-                        match (
-                            egraph[bottom_subst[&"?domain".parse().unwrap()]]
-                                .metadata
-                                .value
-                                .as_ref()
-                                .unwrap(),
-                            egraph[top_subst[&"?domain".parse().unwrap()]]
-                                .metadata
-                                .value
-                                .as_ref()
-                                .unwrap(),
-                        ) {
-                            (
-                                Value::DomainValue(Domain::Long(DomainId::DomainId(d))),
-                                Value::DomainValue(Domain::Long(DomainId::Complement(d_box))),
-                            )
-                            | (
-                                Value::DomainValue(Domain::Toehold(DomainId::DomainId(d))),
-                                Value::DomainValue(Domain::Toehold(DomainId::Complement(d_box))),
-                            )
-                            | (
-                                Value::DomainValue(Domain::Long(DomainId::Complement(d_box))),
-                                Value::DomainValue(Domain::Long(DomainId::DomainId(d))),
-                            )
-                            | (
-                                Value::DomainValue(Domain::Toehold(DomainId::Complement(d_box))),
-                                Value::DomainValue(Domain::Toehold(DomainId::DomainId(d))),
-                            ) => {
-                                // I think there shouldn't be multiple nested complements here.
-                                let d2: DomainIdValue = match **d_box {
-                                    DomainId::DomainId(v) => v,
-                                    _ => panic!(),
-                                };
-                                if *d != d2 {
-                                    return None;
-                                }
-                            }
-                            (Value::DomainValue(_), Value::DomainValue(_)) => return None,
-                            _ => panic!(),
-                        }
+    /// Adds the species to `egraph`, without rebuilding. Returns the id of its
+    /// complex.
+    fn add(&self, egraph: &mut EGraph<Language, Meta>) -> Id {
+        add_complex_cells_to_egraph(egraph, &self.members, &self.strands, &self.pairings())
+    }
+}
 
-                        // If we've made it this far, these things can be stuck
-                        // into a new double cell!
+/// The most copies of one strand instance a complex can hold; see
+/// ReactionRule::can_meet().
+const MAX_COPIES: usize = 2;
 
-                        let mut subst_out = Subst::default();
+/// Whether `eclass` holds a strand instance, i.e. a particular input strand,
+/// rather than a strand cell.
+fn is_strand_instance(egraph: &EGraph<Language, Meta>, eclass: Id) -> bool {
+    match egraph[eclass].metadata.value.as_ref() {
+        Some(Value::StrandValue { .. }) => true,
+        _ => false,
+    }
+}
 
-                        subst_out
-                            .insert(self.next_bottom_strand_cell.clone(), bottom_matches.eclass);
-                        subst_out.insert(self.next_top_strand_cell.clone(), top_matches.eclass);
-                        subst_out.insert(self.previous_double_strand_cell.clone(), eclass);
+/// What a reaction rule does: given its reactants, joined into one species
+/// (see Species::join()), gives every species they can turn into, each with
+/// the name of the reaction which does so. A result which isn't held together
+/// falls apart into several products.
+type React = Rc<dyn Fn(&Species) -> Vec<(String, Species)>>;
 
-                        Some(subst_out)
-                    },
-                )
+/// A reaction rule over whole species, made by reaction_rule().
+#[derive(Clone)]
+struct ReactionRule {
+    react: React,
+    /// Whether the rule acts on single species...
+    unimolecular: bool,
+    /// ...and whether it binds pairs of species together.
+    bimolecular: bool,
+    first: Var,
+    second: Var,
+}
+
+impl ReactionRule {
+    /// What `reactants` can turn into, by which reaction. Each result of a
+    /// bimolecular reaction must hold both reactants together, and anything
+    /// ill-formed is skipped.
+    fn outcomes(&self, reactants: &[Species]) -> Vec<(String, Species)> {
+        let joined: Species = match reactants {
+            [species] if self.unimolecular => species.clone(),
+            [first, second] if self.bimolecular => first.join(second),
+            _ => return vec![],
+        };
+        (self.react)(&joined)
+            .into_iter()
+            .filter(|(_, outcome): &(String, Species)| {
+                let products: Vec<Species> = outcome.components();
+                (reactants.len() == 1 || products.len() == 1)
+                    && products.iter().all(Species::is_well_formed)
+            })
+            .collect()
+    }
+
+    /// Whether the species in `first` and `second` can meet. A species made
+    /// of strand cells can always meet another copy of itself. A strand
+    /// instance stands for a species of its own (see enumerate::enumerate()),
+    /// so species sharing one can meet too, e.g. two copies of one strand, as
+    /// long as no complex would hold more than MAX_COPIES of it; otherwise
+    /// complexes of strand instances could keep growing forever.
+    fn can_meet(
+        egraph: &EGraph<Language, Meta>,
+        first_species: &Species,
+        second_species: &Species,
+    ) -> bool {
+        let members: Vec<Id> = first_species
+            .members
+            .iter()
+            .chain(second_species.members.iter())
+            .cloned()
+            .collect();
+        members.iter().all(|member: &Id| {
+            !is_strand_instance(egraph, *member)
+                || members
+                    .iter()
+                    .filter(|other: &&Id| *other == member)
+                    .count()
+                    <= MAX_COPIES
+        })
+    }
+}
+
+impl Searcher<Language, Meta> for ReactionRule {
+    fn search_eclass(&self, egraph: &EGraph<Language, Meta>, eclass: Id) -> Option<SearchMatches> {
+        let species: Species = Species::find(egraph, eclass)?;
+
+        let mut substs: Vec<Subst> = Vec::default();
+        if self.unimolecular && !self.outcomes(std::slice::from_ref(&species)).is_empty() {
+            let mut subst = Subst::default();
+            subst.insert(self.first.clone(), eclass);
+            substs.push(subst);
+        }
+        if self.bimolecular {
+            let mut others: Vec<Id> = egraph
+                .classes()
+                .map(|other| other.id)
+                .filter(|other: &Id| *other >= eclass)
                 .collect();
+            others.sort();
+            for other in others {
+                let other_species: Species = match Species::find(egraph, other) {
+                    None => continue,
+                    Some(other_species) => other_species,
+                };
+                if ReactionRule::can_meet(egraph, &species, &other_species)
+                    && !self.outcomes(&[species.clone(), other_species]).is_empty()
+                {
+                    let mut subst = Subst::default();
+                    subst.insert(self.first.clone(), eclass);
+                    subst.insert(self.second.clone(), other);
+                    substs.push(subst);
+                }
+            }
+        }
 
+        if substs.is_empty() {
+            None
+        } else {
             Some(SearchMatches {
                 eclass: eclass,
-                substs: substs_out,
+                substs: substs,
             })
         }
     }
+}
+
+/// Adds each reaction a ReactionRule found: every product as a complex, and a
+/// reaction node recording it. Reactions aren't equivalent to their
+/// reactants, so nothing is returned to be unioned with them.
+struct ReactionApplier {
+    rule: ReactionRule,
+}
+
+impl Applier<Language, Meta> for ReactionApplier {
+    fn apply_one(
+        &self,
+        egraph: &mut EGraph<Language, Meta>,
+        _matched_id: Id,
+        subst: &Subst,
+    ) -> Vec<Id> {
+        let reactant_ids: Vec<Id> = std::iter::once(subst.get(&self.rule.first))
+            .chain(std::iter::once(subst.get(&self.rule.second)))
+            .flatten()
+            .cloned()
+            .collect();
+        let reactants: Vec<Species> = match reactant_ids
+            .iter()
+            .map(|id: &Id| Species::find(egraph, *id))
+            .collect()
+        {
+            None => return vec![],
+            Some(reactants) => reactants,
+        };
+
+        for (name, outcome) in self.rule.outcomes(&reactants) {
+            let product_ids: Vec<Id> = outcome
+                .components()
+                .iter()
+                .map(|product: &Species| product.add(egraph))
+                .collect();
+            add_reaction_cells_to_egraph(egraph, &name, &reactant_ids, &product_ids);
+        }
+
+        vec![]
+    }
+}
 
-    struct BindApplier {
-        previous_double_strand_cell: Var,
-        next_bottom_strand_cell: Var,
-        next_top_strand_cell: Var,
+/// A rewrite named `name` which finds, for each species (and, if
+/// `bimolecular`, each pair of species), everything `react` turns it into,
+/// and records each as a reaction named `name`; see enumerate::reactions().
+fn reaction_rule(
+    name: &str,
+    unimolecular: bool,
+    bimolecular: bool,
+    react: impl Fn(&Species) -> Vec<Species> + 'static,
+) -> Rewrite<Language, Meta> {
+    let reaction_name: String = name.to_string();
+    named_reaction_rule(name, unimolecular, bimolecular, move |species: &Species| {
+        react(species)
+            .into_iter()
+            .map(|outcome: Species| (reaction_name.clone(), outcome))
+            .collect()
+    })
+}
+
+/// As reaction_rule(), but `react` names each reaction it finds.
+fn named_reaction_rule(
+    name: &str,
+    unimolecular: bool,
+    bimolecular: bool,
+    react: impl Fn(&Species) -> Vec<(String, Species)> + 'static,
+) -> Rewrite<Language, Meta> {
+    let rule = ReactionRule {
+        react: Rc::new(react),
+        unimolecular: unimolecular,
+        bimolecular: bimolecular,
+        first: "?first".parse().unwrap(),
+        second: "?second".parse().unwrap(),
     };
-    impl Applier<Language, Meta> for BindApplier {
-        fn apply_one(
-            &self,
-            egraph: &mut EGraph<Language, Meta>,
-            _matched_id: Id,
-            subst: &Subst,
-        ) -> Vec<Id> {
-            egraph.add(ENode::new(
-                Language::DoubleStrandCell,
-                vec![
-                    subst[&self.previous_double_strand_cell],
-                    subst[&self.next_bottom_strand_cell],
-                    subst[&self.next_top_strand_cell],
-                ],
-            ));
+    rewrite!(name.to_string();
+             { rule.clone() } =>
+             { ReactionApplier { rule: rule } })
+}
+
+/// Binds a toehold on one species to its complement on another. The rest of
+/// the double strand is left to bind() (or branch_migration()).
+///
+/// Where the invader's branch migration domains aren't directly next to its
+/// toehold (see ToeholdLayout), binding the toehold alone leaves nothing for
+/// bind() to carry on from. So the toehold is also bound together with the
+/// first of those domains, displacing whatever held the template there, as a
+/// reaction named after the layout: ToeholdLayout::penalty() slows it down.
+pub fn toehold_bind() -> Rewrite<Language, Meta> {
+    named_reaction_rule("toehold-bind", false, true, |species: &Species| {
+        let locations: Vec<Location> = species.locations();
+        let mut outcomes: Vec<(String, Species)> = Vec::default();
+        for a in locations.iter() {
+            match species.domain(*a) {
+                Some(Domain::Toehold(..)) => (),
+                _ => continue,
+            }
+            for b in locations.iter() {
+                if a.strand == b.strand || !species.can_pair(*a, *b) {
+                    continue;
+                }
+                outcomes.push((
+                    ToeholdLayout::Adjacent.rule_name(),
+                    species.rebind(&[], &[(*a, *b)]),
+                ));
+                for (layout, branch_migration_end) in species.spaced_toeholds(*a, *b) {
+                    // The invader's last branch migration domain takes the
+                    // template's first from any incumbent.
+                    let template: Location = match species.step(*a, 1) {
+                        None => continue,
+                        Some(template) => template,
+                    };
+                    let displaced: Vec<(Location, Location)> = species
+                        .partner(template)
+                        .map(|incumbent: Location| (template, incumbent))
+                        .into_iter()
+                        .collect();
+                    let unbound: Species = species.rebind(&displaced, &[]);
+                    if unbound.can_pair(template, branch_migration_end) {
+                        outcomes.push((
+                            layout.rule_name(),
+                            unbound.rebind(&[], &[(*a, *b), (template, branch_migration_end)]),
+                        ));
+                    }
+                }
+            }
+        }
+        outcomes
+    })
+}
+
+/// Binds complementary domains next to domains which are already bound to
+/// each other, zipping a double strand up one domain at a time, in either
+/// direction.
+pub fn bind() -> Rewrite<Language, Meta> {
+    reaction_rule("bind", true, false, |species: &Species| {
+        species
+            .directed_pairs()
+            .iter()
+            .filter_map(|(a, b): &(Location, Location)| {
+                let (next_a, next_b): (Location, Location) =
+                    (species.step(*a, 1)?, species.step(*b, -1)?);
+                if species.can_pair(next_a, next_b) {
+                    Some(species.rebind(&[], &[(next_a, next_b)]))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    })
+}
+
+/// Every step of three-way branch migration in `species`: an invader bound
+/// next to an incumbent on the same template takes the incumbent's next
+/// domain, in either direction along the template.
+fn branch_migration_steps(species: &Species) -> Vec<Species> {
+    species
+        .directed_pairs()
+        .iter()
+        .flat_map(|pair: &(Location, Location)| vec![(*pair, 1), (*pair, -1)])
+        .filter_map(
+            |((template, invader), direction): ((Location, Location), isize)| {
+                let next_template: Location = species.step(template, direction)?;
+                let next_invader: Location = species.step(invader, -direction)?;
+                let incumbent: Location = species.partner(next_template)?;
+                if incumbent == next_invader {
+                    return None;
+                }
+                let unbound: Species = species.rebind(&[(next_template, incumbent)], &[]);
+                if unbound.can_pair(next_template, next_invader) {
+                    Some(unbound.rebind(&[], &[(next_template, next_invader)]))
+                } else {
+                    None
+                }
+            },
+        )
+        .collect()
+}
+
+/// Three-way branch migration. An invader bound to a template next to an
+/// incumbent (e.g. by its toehold) displaces the incumbent one domain at a
+/// time. Steps which leave the incumbent with nothing holding it on are
+/// strand_release().
+pub fn branch_migration() -> Rewrite<Language, Meta> {
+    reaction_rule("branch-migration", true, false, |species: &Species| {
+        branch_migration_steps(species)
+            .into_iter()
+            .filter(|outcome: &Species| outcome.components().len() == 1)
+            .collect()
+    })
+}
+
+/// The last step of three-way branch migration, after which the incumbent is
+/// no longer held to the template, and comes off.
+pub fn strand_release() -> Rewrite<Language, Meta> {
+    reaction_rule("strand-release", true, false, |species: &Species| {
+        branch_migration_steps(species)
+            .into_iter()
+            .filter(|outcome: &Species| outcome.components().len() > 1)
+            .collect()
+    })
+}
+
+/// Unbinds double strands which are held together only by toeholds, i.e. the
+/// reverse of toehold_bind(). Toeholds are short enough that they fall off
+/// again; longer ones hold on, so only double strands of at most
+/// `max_toehold_length` nucleotides dissociate. Whatever comes apart is
+/// released as whole species.
+pub fn toehold_dissociation(max_toehold_length: usize) -> Rewrite<Language, Meta> {
+    reaction_rule(
+        "toehold-dissociation",
+        true,
+        false,
+        move |species: &Species| {
+            species
+                .pairings()
+                .iter()
+                .filter(|pairing: &&Pairing| {
+                    total_length(&pairing.domains) <= max_toehold_length
+                        && pairing.domains.iter().all(|domain: &Domain| match domain {
+                            Domain::Toehold(..) => true,
+                            Domain::Long(..) => false,
+                        })
+                })
+                .map(|pairing: &Pairing| species.rebind(&pairing.domain_pairs(), &[]))
+                .collect()
+        },
+    )
+}
+
+/// How many domains at the end of `first_bottom` are complementary to the
+/// start of `second_bottom`; see four_way_branch_migration().
+fn four_way_exchange_length(first_bottom: &[Domain], second_bottom: &[Domain]) -> usize {
+    (1..=std::cmp::min(first_bottom.len(), second_bottom.len()))
+        .rev()
+        .find(|length: &usize| {
+            second_bottom.starts_with(&complementary_strand(
+                &first_bottom[first_bottom.len() - length..],
+            ))
+        })
+        .unwrap_or(0)
+}
+
+/// Four-way (Holliday junction) branch migration between two double strands
+/// of the same complex.
+///
+/// When the end of one double strand's bottom strand (reading it from either
+/// of its strands) is complementary to the start of another's, the two double
+/// strands swap partners over those domains: the two bottom strands bind each
+/// other there, as do the two top strands, and each original double strand is
+/// left holding what's left. The double strands must meet at a junction: both
+/// new double strands stack onto the other two arms of the junction, moving
+/// the branch point.
+pub fn four_way_branch_migration() -> Rewrite<Language, Meta> {
+    /// A pairing's index, read from one of its strands: the domains on that
+    /// strand, and the domain pairs with that strand's first.
+    type Side = (usize, Vec<Domain>, Vec<(Location, Location)>);
+
+    reaction_rule(
+        "four-way-branch-migration",
+        true,
+        false,
+        |species: &Species| {
+            // Each pairing, read from either of its strands.
+            let sides: Vec<Side> = species
+                .pairings()
+                .iter()
+                .enumerate()
+                .flat_map(|(i, pairing): (usize, &Pairing)| {
+                    let pairs: Vec<(Location, Location)> = pairing.domain_pairs();
+                    let flipped: Vec<(Location, Location)> = pairs
+                        .iter()
+                        .rev()
+                        .map(|(bottom, top): &(Location, Location)| (*top, *bottom))
+                        .collect();
+                    vec![
+                        (i, pairing.domains.clone(), pairs),
+                        (i, complementary_strand(&pairing.domains), flipped),
+                    ]
+                })
+                .collect();
+            let mut outcomes: Vec<Species> = Vec::default();
+            for (i, first_domains, first_pairs) in sides.iter() {
+                for (j, second_domains, second_pairs) in sides.iter() {
+                    let length: usize = four_way_exchange_length(first_domains, second_domains);
+                    if i == j || length == 0 {
+                        continue;
+                    }
+                    let exchanged: Vec<(Location, Location)> = first_pairs
+                        [first_pairs.len() - length..]
+                        .iter()
+                        .chain(second_pairs[..length].iter())
+                        .cloned()
+                        .collect();
+                    // The end of the first double strand's bottom strand binds
+                    // the start of the second's, and likewise for the tops.
+                    let swapped: Vec<(Location, Location)> = (0..length)
+                        .flat_map(|k: usize| {
+                            let (first_bottom, first_top) = first_pairs[first_pairs.len() - 1 - k];
+                            let (second_bottom, second_top) = second_pairs[k];
+                            vec![(first_bottom, second_bottom), (first_top, second_top)]
+                        })
+                        .collect();
+                    let at_junction: bool = swapped[..2]
+                        .iter()
+                        .all(|(a, b): &(Location, Location)| species.stacks(*a, *b));
+                    if at_junction {
+                        outcomes.push(species.rebind(&exchanged, &swapped));
+                    }
+                }
+            }
+            outcomes
+        },
+    )
+}
+
+/// Intramolecular binding: a stretch of a strand folds back onto a
+/// complementary stretch further along the same strand, forming a hairpin whose
+/// loop has at least `min_loop_domains` domains. Either side may be overhung by
+/// the rest of the strand, e.g. `t^ x y x*` folds into a hairpin with a toehold
+/// overhang. Only the longest stem each fold can form is zipped up, the same as
+/// toehold_bind() followed by bind() would do for two separate strands.
+pub fn hairpin_formation(min_loop_domains: usize) -> Rewrite<Language, Meta> {
+    reaction_rule(
+        "hairpin-formation",
+        true,
+        false,
+        move |species: &Species| {
+            let min_loop_domains: usize = std::cmp::max(min_loop_domains, 1);
+            // Whether the i-th domain from the start and the j-th can close a
+            // stem with a long enough loop between them.
+            let can_close = |strand: usize, i: usize, j: usize| -> bool {
+                i < j
+                    && j - i > min_loop_domains
+                    && species.can_pair(
+                        Location {
+                            strand: strand,
+                            domain: i,
+                        },
+                        Location {
+                            strand: strand,
+                            domain: j,
+                        },
+                    )
+            };
+
+            let mut outcomes: Vec<Species> = Vec::default();
+            for (strand, domains) in species.strands.iter().enumerate() {
+                for i in 0..domains.len() {
+                    for j in i + 1..domains.len() {
+                        // Only start from the outermost pair of a stem, so that
+                        // each hairpin is formed once.
+                        if !can_close(strand, i, j)
+                            || (i > 0 && j + 1 < domains.len() && can_close(strand, i - 1, j + 1))
+                        {
+                            continue;
+                        }
+
+                        let stem: Vec<(Location, Location)> = (0..)
+                            .take_while(|k: &usize| can_close(strand, i + k, j - k))
+                            .map(|k: usize| {
+                                (
+                                    Location {
+                                        strand: strand,
+                                        domain: i + k,
+                                    },
+                                    Location {
+                                        strand: strand,
+                                        domain: j - k,
+                                    },
+                                )
+                            })
+                            .collect();
+                        outcomes.push(species.rebind(&[], &stem));
+                    }
+                }
+            }
+            outcomes
+        },
+    )
+}
+
+/// Binds two copies of the same strand to each other by a toehold, where the
+/// strand carries both the toehold and its complement (a homodimer). The
+/// copies are symmetric, so where the first's toehold binds the second's
+/// complement, the second's toehold binds the first's complement too. Copies
+/// of a strand given as strand instances (see add_strand_instance_to_egraph())
+/// are told apart, so the product can be traced back to both of them with
+/// strand_ids(), and reads as `A + A -> AA`.
+pub fn homodimer_bind() -> Rewrite<Language, Meta> {
+    reaction_rule("homodimer-bind", false, true, |species: &Species| {
+        match species.strands.as_slice() {
+            [first, second] if first == second && species.pairs.is_empty() => (),
+            _ => return vec![],
+        }
+        let locations: Vec<Location> = species.locations();
+        let mut outcomes: Vec<Species> = Vec::default();
+        for a in locations.iter().filter(|a: &&Location| a.strand == 0) {
+            match species.domain(*a) {
+                Some(Domain::Toehold(..)) => (),
+                _ => continue,
+            }
+            for b in locations.iter().filter(|b: &&Location| b.strand == 1) {
+                if !species.can_pair(*a, *b) {
+                    continue;
+                }
+                let mirror_a = Location {
+                    strand: 1,
+                    domain: a.domain,
+                };
+                let mirror_b = Location {
+                    strand: 0,
+                    domain: b.domain,
+                };
+                outcomes.push(species.rebind(&[], &[(*a, *b), (mirror_a, mirror_b)]));
+            }
+        }
+        outcomes
+    })
+}
+
+/// How an invader's toehold is joined to its branch migration domains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToeholdLayout {
+    /// Directly next to them, as toehold_bind() and bind() expect.
+    Adjacent,
+    /// Joined to them by this many spacer domains on the invader.
+    Remote(usize),
+    /// On a separate strand, held to the invader by a bridging double strand.
+    Associative,
+}
+
+impl ToeholdLayout {
+    /// The name of the reactions, found by toehold_bind(), which bind toeholds
+    /// of this layout.
+    pub fn rule_name(&self) -> String {
+        match self {
+            ToeholdLayout::Adjacent => "toehold-bind".to_string(),
+            ToeholdLayout::Remote(spacer_domains) => {
+                format!("remote-toehold-bind-{}", spacer_domains)
+            }
+            ToeholdLayout::Associative => "associative-toehold-bind".to_string(),
+        }
+    }
 
-            vec![]
+    pub fn from_rule_name(name: &str) -> Option<ToeholdLayout> {
+        match name {
+            "toehold-bind" => Some(ToeholdLayout::Adjacent),
+            "associative-toehold-bind" => Some(ToeholdLayout::Associative),
+            _ if name.starts_with("remote-toehold-bind-") => name["remote-toehold-bind-".len()..]
+                .parse()
+                .ok()
+                .map(ToeholdLayout::Remote),
+            _ => None,
         }
     }
 
-    rewrite!("bind";
-             { BindSearcher {
-                 previous_double_strand_cell: "?previous-double-strand-cell".parse().unwrap(),
-                 next_bottom_strand_cell: "?next-bottom-strand-cell".parse().unwrap(),
-                 next_top_strand_cell: "?next-top-strand-cell".parse().unwrap(),
-             } } =>
-             { BindApplier {
-                 previous_double_strand_cell: "?previous-double-strand-cell".parse().unwrap(),
-                 next_bottom_strand_cell: "?next-bottom-strand-cell".parse().unwrap(),
-                 next_top_strand_cell: "?next-top-strand-cell".parse().unwrap(),
-             } })
+    /// Factor by which binding through a toehold of this layout is slower than
+    /// through an adjacent one. The spacer of a remote toehold forms a loop,
+    /// which we penalize like a Jacobson-Stockmayer loop closure; associative
+    /// toeholds are roughly an order of magnitude slower again.
+    pub fn penalty(&self) -> f64 {
+        match self {
+            ToeholdLayout::Adjacent => 1.0,
+            ToeholdLayout::Remote(spacer_domains) => (1.0 + *spacer_domains as f64).powf(-1.5),
+            ToeholdLayout::Associative => 0.1,
+        }
+    }
 }
 
-pub fn run(egraph: &mut EGraph<Language, Meta>, rules: &[Rewrite<Language, Meta>]) {
+/// Runs `rules` until the egraph stops growing. Fails, without running any
+/// rules, if the egraph already holds something ill-formed, and stops as soon
+/// as a rule produces something ill-formed.
+pub fn run(
+    egraph: &mut EGraph<Language, Meta>,
+    rules: &[Rewrite<Language, Meta>],
+) -> Result<(), MetaError> {
+    if let Some(error) = find_error(egraph) {
+        return Err(error);
+    }
+
     let mut egraph_size = egraph.total_size();
     loop {
         run_one(egraph, rules);
+        if let Some(error) = find_error(egraph) {
+            return Err(error);
+        }
         if egraph_size == egraph.total_size() {
             break;
         }
 
         egraph_size = egraph.total_size();
     }
+
+    Ok(())
 }
 
 fn run_one(egraph: &mut EGraph<Language, Meta>, rules: &[Rewrite<Language, Meta>]) {
@@ -454,6 +1032,7 @@ fn run_one(egraph: &mut EGraph<Language, Meta>, rules: &[Rewrite<Language, Meta>
 
 #[cfg(test)]
 mod tests {
+    use super::super::enumerate::{reactions, species, Reaction};
     use super::*;
     use egg::{EGraph, ENode, Pattern, Runner, Searcher};
 
@@ -461,21 +1040,21 @@ mod tests {
     fn simplify_double_complement() {
         let mut egraph = EGraph::default();
 
-        let enode_id = egraph.add(ENode::leaf(Language::DomainIdValue(0)));
+        let enode_id = egraph.add(ENode::leaf(Language::DomainIdValue(Symbol::Number(0))));
         let enode_id = egraph.add(ENode::new(Language::Complement, vec![enode_id]));
         let enode_id = egraph.add(ENode::new(Language::Complement, vec![enode_id]));
 
         assert!(!egraph[enode_id]
             .nodes
             .iter()
-            .any(|enode| { enode.op == Language::DomainIdValue(0) }));
+            .any(|enode| { enode.op == Language::DomainIdValue(Symbol::Number(0)) }));
         let runner = Runner::new()
             .with_egraph(egraph)
             .run(&[super::simplify_double_complement()]);
         assert!(runner.egraph[enode_id]
             .nodes
             .iter()
-            .any(|enode| { enode.op == Language::DomainIdValue(0) }));
+            .any(|enode| { enode.op == Language::DomainIdValue(Symbol::Number(0)) }));
     }
 
     #[test]
@@ -484,15 +1063,16 @@ mod tests {
         add_strand_to_egraph(
             &mut egraph,
             &vec![
-                Domain::Long(DomainId::DomainId(5)),
-                Domain::Long(DomainId::DomainId(4)),
-                Domain::Toehold(DomainId::DomainId(0)),
-                Domain::Long(DomainId::DomainId(1)),
-                Domain::Long(DomainId::DomainId(2)),
-                Domain::Long(DomainId::Complement(Box::new(DomainId::DomainId(2)))),
-                Domain::Long(DomainId::DomainId(3)),
+                Domain::long(DomainId::DomainId(5)),
+                Domain::long(DomainId::DomainId(4)),
+                Domain::toehold(DomainId::DomainId(0)),
+                Domain::long(DomainId::DomainId(1)),
+                Domain::long(DomainId::DomainId(2)),
+                Domain::long(DomainId::Complement(Box::new(DomainId::DomainId(2)))),
+                Domain::long(DomainId::DomainId(3)),
             ],
-        );
+        )
+        .unwrap();
 
         let mut rws = Vec::default();
         rws.extend(strand_cell_associativity());
@@ -589,32 +1169,62 @@ mod tests {
         );
     }
 
+    /// A pairing from `bottom` to `top`, given as (strand, domain), over
+    /// `domains` in compact notation.
+    fn pairing(bottom: (usize, usize), top: (usize, usize), domains: &str) -> Pairing {
+        Pairing {
+            bottom: Location {
+                strand: bottom.0,
+                domain: bottom.1,
+            },
+            top: Location {
+                strand: top.0,
+                domain: top.1,
+            },
+            domains: parse_domains(domains).unwrap(),
+        }
+    }
+
+    /// The value of the complex of `strands` (in compact notation) bound by
+    /// `pairings`.
+    fn complex_value(strands: &[&str], pairings: &[Pairing]) -> Value {
+        let mut egraph = EGraph::<Language, Meta>::default();
+        let strands: Vec<Vec<Domain>> = strands
+            .iter()
+            .map(|strand: &&str| parse_domains(strand).unwrap())
+            .collect();
+        let id: Id = add_complex_to_egraph(&mut egraph, &strands, &pairings.to_vec()).unwrap();
+        egraph[id].metadata.value.clone().unwrap()
+    }
+
+    fn add_complex(egraph: &mut EGraph<Language, Meta>, strands: &[&str], pairings: &[Pairing]) {
+        let strands: Vec<Vec<Domain>> = strands
+            .iter()
+            .map(|strand: &&str| parse_domains(strand).unwrap())
+            .collect();
+        add_complex_to_egraph(egraph, &strands, &pairings.to_vec()).unwrap();
+    }
+
+    fn reaction(rule: &str, mut reactants: Vec<Value>, mut products: Vec<Value>) -> Reaction {
+        reactants.sort();
+        products.sort();
+        Reaction {
+            reactants: reactants,
+            products: products,
+            rule: rule.to_string(),
+            rate: None,
+        }
+    }
+
     #[test]
     fn toehold_bind_and_bind() {
         test_logger::ensure_env_logger_initialized();
 
+        let template: &str = "5 t0^ 1 2 3";
+        let invader: &str = "4 2* 1* t0^* 5*";
         let mut egraph = EGraph::<Language, Meta>::default();
-        add_strand_to_egraph(
-            &mut egraph,
-            &vec![
-                Domain::Long(DomainId::DomainId(5)),
-                Domain::Toehold(DomainId::DomainId(0)),
-                Domain::Long(DomainId::DomainId(1)),
-                Domain::Long(DomainId::DomainId(2)),
-                Domain::Long(DomainId::DomainId(3)),
-            ],
-        );
-
-        add_strand_to_egraph(
-            &mut egraph,
-            &vec![
-                Domain::Long(DomainId::DomainId(4)),
-                Domain::Long(DomainId::Complement(Box::new(DomainId::DomainId(2)))),
-                Domain::Long(DomainId::Complement(Box::new(DomainId::DomainId(1)))),
-                Domain::Toehold(DomainId::Complement(Box::new(DomainId::DomainId(0)))),
-                Domain::Long(DomainId::Complement(Box::new(DomainId::DomainId(5)))),
-            ],
-        );
+        add_strand_to_egraph(&mut egraph, &parse_domains(template).unwrap()).unwrap();
+        add_strand_to_egraph(&mut egraph, &parse_domains(invader).unwrap()).unwrap();
 
         // Rewrite strands to all their equivalent forms
         let mut rws = Vec::default();
@@ -632,31 +1242,449 @@ mod tests {
             .to_svg("toehold-bind-and-bind.svg")
             .unwrap();
 
+        let found: Vec<Reaction> = reactions(&runner.egraph, None);
+        assert!(found.contains(&reaction(
+            "toehold-bind",
+            vec![
+                complex_value(&[template], &[]),
+                complex_value(&[invader], &[])
+            ],
+            vec![complex_value(
+                &[template, invader],
+                &[pairing((0, 1), (1, 3), "t0^")]
+            )],
+        )));
+        // The toehold zips up in both directions, as far as it can go.
+        let zipped: Value = complex_value(
+            &[template, invader],
+            &[pairing((0, 0), (1, 1), "5 t0^ 1 2")],
+        );
+        assert!(species(&found).contains(&zipped));
+        assert!(found
+            .iter()
+            .all(|reaction: &Reaction| reaction.products.len() == 1));
+        // Every pairing is also a double strand.
         assert_eq!(
-            "(double-strand-cell
-              (strand-cell (domain (toehold-domain (domain-id 0))) nil)
-              (strand-cell (domain (toehold-domain (complement (domain-id 0)))) nil)
-              nil)"
-                .parse::<Pattern<Language>>()
-                .unwrap()
-                .search(&runner.egraph)
+            find_double_strand_cells(&runner.egraph, &parse_domains("2* 1* t0^* 5*").unwrap())
                 .len(),
             1
         );
+    }
+
+    #[test]
+    fn branch_migration() {
+        test_logger::ensure_env_logger_initialized();
+
+        let template: &str = "t0^ 1 2";
+        let invader: &str = "2* 1* t0^*";
+        let incumbent: &str = "2* 1*";
+        let mut egraph = EGraph::<Language, Meta>::default();
+        add_strand_to_egraph(&mut egraph, &parse_domains(invader).unwrap()).unwrap();
+        add_complex(
+            &mut egraph,
+            &[template, incumbent],
+            &[pairing((0, 1), (1, 0), "1 2")],
+        );
+
+        // No bind() here: the invader can only get past its toehold by
+        // displacing the incumbent.
+        let rws = vec![toehold_bind(), super::branch_migration()];
+        let runner = Runner::new().with_egraph(egraph).run(&rws);
+        let found: Vec<Reaction> = reactions(&runner.egraph, None);
+
+        // The invader migrates along the template, leaving the incumbent
+        // holding just its last domain...
+        let toehold_bound: Value = complex_value(
+            &[template, invader, incumbent],
+            &[
+                pairing((0, 0), (1, 2), "t0^"),
+                pairing((0, 1), (2, 0), "1 2"),
+            ],
+        );
+        let migrated: Value = complex_value(
+            &[template, invader, incumbent],
+            &[
+                pairing((0, 0), (1, 1), "t0^ 1"),
+                pairing((0, 2), (2, 0), "2"),
+            ],
+        );
+        assert!(found.contains(&reaction(
+            "branch-migration",
+            vec![toehold_bound.clone()],
+            vec![migrated.clone()],
+        )));
+        // ...and can migrate back again.
+        assert!(found.contains(&reaction(
+            "branch-migration",
+            vec![migrated.clone()],
+            vec![toehold_bound.clone()],
+        )));
+        // Only strand_release() takes the last domain.
+        assert!(found
+            .iter()
+            .all(|reaction: &Reaction| reaction.products.len() == 1));
+    }
+
+    #[test]
+    fn strand_release() {
+        let template: &str = "t0^ 1 2";
+        let invader: &str = "2* 1* t0^*";
+        let incumbent: &str = "2* 1*";
+        let mut egraph = EGraph::<Language, Meta>::default();
+        add_strand_to_egraph(&mut egraph, &parse_domains(invader).unwrap()).unwrap();
+        add_complex(
+            &mut egraph,
+            &[template, incumbent],
+            &[pairing((0, 1), (1, 0), "1 2")],
+        );
+
+        let rws = vec![
+            toehold_bind(),
+            super::branch_migration(),
+            super::strand_release(),
+        ];
+        let runner = Runner::new().with_egraph(egraph).run(&rws);
+        let found: Vec<Reaction> = reactions(&runner.egraph, None);
+
+        // The invader binds its toehold, migrates one domain, then takes the
+        // last one, and the incumbent comes off whole.
+        let migrated: Value = complex_value(
+            &[template, invader, incumbent],
+            &[
+                pairing((0, 0), (1, 1), "t0^ 1"),
+                pairing((0, 2), (2, 0), "2"),
+            ],
+        );
+        assert!(found.contains(&reaction(
+            "toehold-bind",
+            vec![
+                complex_value(&[invader], &[]),
+                complex_value(&[template, incumbent], &[pairing((0, 1), (1, 0), "1 2")]),
+            ],
+            vec![complex_value(
+                &[template, invader, incumbent],
+                &[
+                    pairing((0, 0), (1, 2), "t0^"),
+                    pairing((0, 1), (2, 0), "1 2"),
+                ],
+            )],
+        )));
+        assert!(species(&found).contains(&migrated));
+        let released: Vec<&Reaction> = found
+            .iter()
+            .filter(|reaction: &&Reaction| reaction.rule == "strand-release")
+            .collect();
         assert_eq!(
-            "(double-strand-cell
-              (double-strand-cell
-               (strand-cell ?a ?b)
-               (strand-cell ?c ?d)
-               ?rest)
-              (strand-cell ?e ?f)
-              (strand-cell ?g ?h)
-             )"
-            .parse::<Pattern<Language>>()
-            .unwrap()
-            .search(&runner.egraph)
-            .len(),
-            3
+            released,
+            vec![&reaction(
+                "strand-release",
+                vec![migrated.clone()],
+                vec![
+                    complex_value(&[template, invader], &[pairing((0, 0), (1, 0), "t0^ 1 2")]),
+                    complex_value(&[incumbent], &[]),
+                ],
+            )]
+        );
+    }
+
+    #[test]
+    fn toehold_dissociation() {
+        let mut egraph = EGraph::<Language, Meta>::default();
+        add_complex(
+            &mut egraph,
+            &["t2^", "t2^*"],
+            &[pairing((0, 0), (1, 0), "t2^")],
+        );
+        // Too long to fall off.
+        add_complex(
+            &mut egraph,
+            &["t0^ t1^", "t1^* t0^*"],
+            &[pairing((0, 0), (1, 0), "t0^ t1^")],
+        );
+        // Held by more than its toehold.
+        add_complex(
+            &mut egraph,
+            &["t3^ 4", "4* t3^*"],
+            &[pairing((0, 0), (1, 0), "t3^ 4")],
+        );
+
+        let runner = Runner::new()
+            .with_egraph(egraph)
+            .run(&[super::toehold_dissociation(DEFAULT_TOEHOLD_LENGTH)]);
+
+        assert_eq!(
+            reactions(&runner.egraph, None),
+            vec![reaction(
+                "toehold-dissociation",
+                vec![complex_value(
+                    &["t2^", "t2^*"],
+                    &[pairing((0, 0), (1, 0), "t2^")]
+                )],
+                vec![complex_value(&["t2^"], &[]), complex_value(&["t2^*"], &[])],
+            )]
+        );
+    }
+
+    #[test]
+    fn toehold_bind_and_dissociation() {
+        let template: &str = "5 t0^ 1";
+        let invader: &str = "t0^* 6";
+        let mut egraph = EGraph::<Language, Meta>::default();
+        for strand in &[template, invader, "t1:9^ 2", "t1:9^*"] {
+            add_strand_to_egraph(&mut egraph, &parse_domains(strand).unwrap()).unwrap();
+        }
+
+        let rws = vec![
+            toehold_bind(),
+            bind(),
+            super::toehold_dissociation(DEFAULT_TOEHOLD_LENGTH),
+        ];
+        let runner = Runner::new().with_egraph(egraph).run(&rws);
+
+        // The short toehold falls off again, leaving both strands whole; the
+        // longer one holds on.
+        let dissociations: Vec<Reaction> = reactions(&runner.egraph, None)
+            .into_iter()
+            .filter(|reaction: &Reaction| reaction.rule == "toehold-dissociation")
+            .collect();
+        assert_eq!(
+            dissociations,
+            vec![reaction(
+                "toehold-dissociation",
+                vec![complex_value(
+                    &[template, invader],
+                    &[pairing((0, 1), (1, 0), "t0^")]
+                )],
+                vec![
+                    complex_value(&[template], &[]),
+                    complex_value(&[invader], &[])
+                ],
+            )]
+        );
+    }
+
+    #[test]
+    fn four_way_branch_migration() {
+        // A Holliday junction, with domain 2 on either side of it.
+        let strands: &[&str] = &["1 2 3", "3* 2* 4", "4* 2 5", "5* 2* 1*"];
+        let junction: &[Pairing] = &[
+            pairing((0, 0), (3, 1), "1 2"),
+            pairing((0, 2), (1, 0), "3"),
+            pairing((1, 1), (2, 0), "2* 4"),
+            pairing((2, 2), (3, 0), "5"),
+        ];
+        let mut egraph = EGraph::<Language, Meta>::default();
+        add_complex(&mut egraph, strands, junction);
+        // Two double strands which could exchange, but aren't joined.
+        add_complex(
+            &mut egraph,
+            &["6 7", "7* 6*"],
+            &[pairing((0, 0), (1, 0), "6 7")],
+        );
+        add_complex(
+            &mut egraph,
+            &["7* 8", "8* 7"],
+            &[pairing((0, 0), (1, 0), "7* 8")],
+        );
+
+        let runner = Runner::new()
+            .with_egraph(egraph)
+            .run(&[super::four_way_branch_migration()]);
+
+        // The branch point moves past domain 2, and back again.
+        let migrated: Value = complex_value(
+            strands,
+            &[
+                pairing((0, 0), (3, 2), "1"),
+                pairing((0, 1), (1, 0), "2 3"),
+                pairing((1, 2), (2, 0), "4"),
+                pairing((2, 1), (3, 0), "2 5"),
+            ],
+        );
+        assert_eq!(
+            reactions(&runner.egraph, None),
+            vec![
+                reaction(
+                    "four-way-branch-migration",
+                    vec![complex_value(strands, junction)],
+                    vec![migrated.clone()],
+                ),
+                reaction(
+                    "four-way-branch-migration",
+                    vec![migrated.clone()],
+                    vec![complex_value(strands, junction)],
+                ),
+            ]
+        );
+    }
+
+    /// The reactions in `egraph` named `rule`.
+    fn reactions_named(egraph: &EGraph<Language, Meta>, rule: &str) -> Vec<Reaction> {
+        reactions(egraph, None)
+            .into_iter()
+            .filter(|reaction: &Reaction| reaction.rule == rule)
+            .collect()
+    }
+
+    #[test]
+    fn remote_toehold_bind() {
+        let template: &str = "t0^ 1 2";
+        // A spacer between the toehold and the branch migration domains.
+        let invader: &str = "2* 1* 5 t0^*";
+        // The same, but with its spacer bound, so it can't loop out.
+        let blocked: &str = "2* 1* 7 t0^*";
+        let mut egraph = EGraph::<Language, Meta>::default();
+        add_strand_to_egraph(&mut egraph, &parse_domains(template).unwrap()).unwrap();
+        add_strand_to_egraph(&mut egraph, &parse_domains(invader).unwrap()).unwrap();
+        add_complex(
+            &mut egraph,
+            &[blocked, "7*"],
+            &[pairing((0, 2), (1, 0), "7")],
         );
+
+        let rws = vec![toehold_bind(), bind()];
+        let runner = Runner::new().with_egraph(egraph).run(&rws);
+
+        // The toehold binds along with the first branch migration domain, and
+        // bind() zips up the rest.
+        assert_eq!(
+            reactions_named(&runner.egraph, "remote-toehold-bind-1"),
+            vec![reaction(
+                "remote-toehold-bind-1",
+                vec![
+                    complex_value(&[template], &[]),
+                    complex_value(&[invader], &[])
+                ],
+                vec![complex_value(
+                    &[template, invader],
+                    &[pairing((0, 0), (1, 3), "t0^"), pairing((0, 1), (1, 1), "1")]
+                )],
+            )]
+        );
+        assert!(
+            species(&reactions(&runner.egraph, None)).contains(&complex_value(
+                &[template, invader],
+                &[
+                    pairing((0, 0), (1, 3), "t0^"),
+                    pairing((0, 1), (1, 0), "1 2")
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn repeated_domain_next_to_toehold() {
+        let template: &str = "t0^ 1 2";
+        // Domain 1* is right next to the toehold, and again further along.
+        let invader: &str = "1* 2* 1* t0^*";
+        let mut egraph = EGraph::<Language, Meta>::default();
+        add_strand_to_egraph(&mut egraph, &parse_domains(template).unwrap()).unwrap();
+        add_strand_to_egraph(&mut egraph, &parse_domains(invader).unwrap()).unwrap();
+
+        let runner = Runner::new()
+            .with_egraph(egraph)
+            .run(&[toehold_bind(), bind()]);
+
+        // The toehold is adjacent, so it isn't penalised as a remote one.
+        let found: Vec<Reaction> = reactions(&runner.egraph, None);
+        assert!(found.iter().all(|reaction: &Reaction| {
+            match ToeholdLayout::from_rule_name(&reaction.rule) {
+                Some(ToeholdLayout::Remote(_)) => false,
+                _ => true,
+            }
+        }));
+        assert!(found
+            .iter()
+            .any(|reaction: &Reaction| reaction.rule == "toehold-bind"));
+        assert!(species(&found).contains(&complex_value(
+            &[template, invader],
+            &[pairing((0, 0), (1, 2), "t0^ 1")]
+        )));
+    }
+
+    #[test]
+    fn associative_toehold_bind() {
+        let template: &str = "t0^ 1";
+        let invader: &str = "1* 6";
+        // Toehold, bridged to the invader by domain 6.
+        let holder: &str = "6* t0^*";
+        let mut egraph = EGraph::<Language, Meta>::default();
+        add_strand_to_egraph(&mut egraph, &parse_domains(template).unwrap()).unwrap();
+        let bridged: Pairing = pairing((0, 1), (1, 0), "6");
+        add_complex(
+            &mut egraph,
+            &[invader, holder],
+            std::slice::from_ref(&bridged),
+        );
+        // Not bridged to anything.
+        add_strand_to_egraph(&mut egraph, &parse_domains("6 t0^*").unwrap()).unwrap();
+
+        let runner = Runner::new().with_egraph(egraph).run(&[toehold_bind()]);
+
+        assert_eq!(
+            reactions_named(&runner.egraph, "associative-toehold-bind"),
+            vec![reaction(
+                "associative-toehold-bind",
+                vec![
+                    complex_value(&[template], &[]),
+                    complex_value(&[invader, holder], &[bridged]),
+                ],
+                vec![complex_value(
+                    &[template, invader, holder],
+                    &[
+                        pairing((0, 0), (2, 1), "t0^"),
+                        pairing((0, 1), (1, 0), "1"),
+                        pairing((1, 1), (2, 0), "6"),
+                    ]
+                )],
+            )]
+        );
+    }
+
+    #[test]
+    fn toehold_layout_rule_names() {
+        for layout in &[
+            ToeholdLayout::Adjacent,
+            ToeholdLayout::Remote(3),
+            ToeholdLayout::Associative,
+        ] {
+            assert_eq!(
+                ToeholdLayout::from_rule_name(&layout.rule_name()),
+                Some(*layout)
+            );
+        }
+        assert_eq!(ToeholdLayout::from_rule_name("bind"), None);
+        assert!(ToeholdLayout::Remote(2).penalty() < ToeholdLayout::Remote(1).penalty());
+    }
+
+    #[test]
+    fn hairpin_formation() {
+        let hairpins = |strand: &str| -> Vec<Value> {
+            let mut egraph = EGraph::<Language, Meta>::default();
+            add_strand_to_egraph(&mut egraph, &parse_domains(strand).unwrap()).unwrap();
+
+            let runner = Runner::new()
+                .with_egraph(egraph)
+                .run(&[super::hairpin_formation(1)]);
+            species(&reactions(&runner.egraph, None))
+        };
+
+        // A whole strand folds into its longest stem, not just part of it.
+        let strand: &str = "t0^ 1 2 1* t0^*";
+        let folded: Vec<Value> = hairpins(strand);
+        assert!(folded.contains(&complex_value(
+            &[strand],
+            &[pairing((0, 0), (0, 3), "t0^ 1")]
+        )));
+        assert_eq!(folded.len(), 2);
+
+        // A hairpin with a toehold overhang.
+        let strand: &str = "t0^ 1 2 1*";
+        let folded: Vec<Value> = hairpins(strand);
+        assert!(folded.contains(&complex_value(&[strand], &[pairing((0, 1), (0, 3), "1")])));
+        assert_eq!(folded.len(), 2);
+
+        // The loop must be at least one domain long.
+        assert_eq!(hairpins("t0^ 1 1* 2").len(), 0);
     }
 }
@@ -0,0 +1,243 @@
+//! Export of enumerated reaction networks as SBML Level 3 Version 2, for
+//! COPASI and other standard simulators.
+
+use super::crn::{species_name, species_structure};
+use super::enumerate::{species, Reaction};
+use super::*;
+
+/// Namespace of the annotations giving each species' domain-level structure.
+pub const ANNOTATION_NAMESPACE: &str = "https://github.com/gussmith23/egg-dsd";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Writes `reactions` out as an SBML model with id `model_id`, in a single
+/// one-litre compartment. Species start at the concentrations (in M) given in
+/// `initial`, or zero. Reactions are mass-action, with their rate as a local
+/// parameter `k`; reactions without a rate get no kinetic law.
+///
+/// Each species is annotated with its structure (see crn::species_structure()).
+pub fn to_sbml(reactions: &[Reaction], initial: &[(Value, f64)], model_id: &str) -> String {
+    let mut all_species: Vec<Value> = species(reactions);
+    for (value, _) in initial {
+        if !all_species.contains(value) {
+            all_species.push(value.clone());
+        }
+    }
+
+    let mut out = String::default();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(
+        "<sbml xmlns=\"http://www.sbml.org/sbml/level3/version2/core\" level=\"3\" version=\"2\">\n",
+    );
+    out.push_str(&format!(
+        "  <model id=\"{}\" substanceUnits=\"mole\" timeUnits=\"second\" volumeUnits=\"litre\" extentUnits=\"mole\">\n",
+        escape(model_id)
+    ));
+    out.push_str("    <listOfCompartments>\n");
+    out.push_str(
+        "      <compartment id=\"solution\" spatialDimensions=\"3\" size=\"1\" constant=\"true\"/>\n",
+    );
+    out.push_str("    </listOfCompartments>\n");
+
+    out.push_str("    <listOfSpecies>\n");
+    for value in all_species.iter() {
+        let concentration: f64 = initial
+            .iter()
+            .filter(|(v, _)| v == value)
+            .fold(0.0, |total: f64, (_, concentration)| total + concentration);
+        out.push_str(&format!(
+            "      <species id=\"{}\" compartment=\"solution\" initialConcentration=\"{:e}\" hasOnlySubstanceUnits=\"false\" boundaryCondition=\"false\" constant=\"false\">\n",
+            species_name(value),
+            concentration
+        ));
+        out.push_str("        <annotation>\n");
+        out.push_str(&format!(
+            "          <egg-dsd:structure xmlns:egg-dsd=\"{}\">{}</egg-dsd:structure>\n",
+            ANNOTATION_NAMESPACE,
+            escape(&species_structure(value))
+        ));
+        out.push_str("        </annotation>\n");
+        out.push_str("      </species>\n");
+    }
+    out.push_str("    </listOfSpecies>\n");
+
+    out.push_str("    <listOfReactions>\n");
+    for (i, reaction) in reactions.iter().enumerate() {
+        out.push_str(&format!(
+            "      <reaction id=\"r{}\" name=\"{}\" reversible=\"false\">\n",
+            i,
+            escape(&reaction.rule)
+        ));
+        for (list, values) in &[
+            ("listOfReactants", &reaction.reactants),
+            ("listOfProducts", &reaction.products),
+        ] {
+            if values.is_empty() {
+                continue;
+            }
+            out.push_str(&format!("        <{}>\n", list));
+            let mut names: Vec<String> = values.iter().map(species_name).collect();
+            names.sort();
+            names.dedup();
+            for name in names.iter() {
+                let stoichiometry: usize = values
+                    .iter()
+                    .filter(|value: &&Value| species_name(value) == *name)
+                    .count();
+                out.push_str(&format!(
+                    "          <speciesReference species=\"{}\" stoichiometry=\"{}\" constant=\"true\"/>\n",
+                    name, stoichiometry
+                ));
+            }
+            out.push_str(&format!("        </{}>\n", list));
+        }
+        if let Some(rate) = reaction.rate {
+            // Kinetic laws are in amounts per time, so scale concentrations
+            // back up by the compartment's volume.
+            out.push_str("        <kineticLaw>\n");
+            out.push_str("          <math xmlns=\"http://www.w3.org/1998/Math/MathML\">\n");
+            out.push_str("            <apply>\n");
+            out.push_str("              <times/>\n");
+            out.push_str("              <ci> k </ci>\n");
+            out.push_str("              <ci> solution </ci>\n");
+            for value in reaction.reactants.iter() {
+                out.push_str(&format!(
+                    "              <ci> {} </ci>\n",
+                    species_name(value)
+                ));
+            }
+            out.push_str("            </apply>\n");
+            out.push_str("          </math>\n");
+            out.push_str("          <listOfLocalParameters>\n");
+            out.push_str(&format!(
+                "            <localParameter id=\"k\" value=\"{:e}\"/>\n",
+                rate
+            ));
+            out.push_str("          </listOfLocalParameters>\n");
+            out.push_str("        </kineticLaw>\n");
+        }
+        out.push_str("      </reaction>\n");
+    }
+    out.push_str("    </listOfReactions>\n");
+
+    out.push_str("  </model>\n");
+    out.push_str("</sbml>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::enumerate::enumerate;
+    use super::super::kinetics::RateModel;
+    use super::super::rewrites::*;
+    use super::*;
+
+    #[test]
+    fn toehold_sbml() {
+        let input: Vec<Domain> = parse_domains("t0^ 1").unwrap();
+        let reactions: Vec<Reaction> = enumerate(
+            &[input.clone(), parse_domains("t0^*").unwrap()],
+            &[],
+            &[
+                toehold_bind(),
+                bind(),
+                toehold_dissociation(DEFAULT_TOEHOLD_LENGTH),
+            ],
+            Some(&RateModel::default()),
+        )
+        .unwrap();
+        let free_input = Value::ComplexValue {
+            strands: vec![input],
+            pairings: vec![],
+        };
+
+        let sbml: String = to_sbml(&reactions, &[(free_input, 1e-6)], "toehold");
+        // Every species is a complex, named as in crn::species_name(), and only
+        // the input strand starts with anything.
+        assert_eq!(reactions[1].rule, "toehold-dissociation");
+        assert_eq!(
+            sbml,
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<sbml xmlns="http://www.sbml.org/sbml/level3/version2/core" level="3" version="2">
+  <model id="toehold" substanceUnits="mole" timeUnits="second" volumeUnits="litre" extentUnits="mole">
+    <listOfCompartments>
+      <compartment id="solution" spatialDimensions="3" size="1" constant="true"/>
+    </listOfCompartments>
+    <listOfSpecies>
+      <species id="t0_0" compartment="solution" initialConcentration="0e0" hasOnlySubstanceUnits="false" boundaryCondition="false" constant="false">
+        <annotation>
+          <egg-dsd:structure xmlns:egg-dsd="https://github.com/gussmith23/egg-dsd">&lt;t0^*&gt;</egg-dsd:structure>
+        </annotation>
+      </species>
+      <species id="complex_2_t0__0_t0__d1_p0_0_1_0_1" compartment="solution" initialConcentration="0e0" hasOnlySubstanceUnits="false" boundaryCondition="false" constant="false">
+        <annotation>
+          <egg-dsd:structure xmlns:egg-dsd="https://github.com/gussmith23/egg-dsd">&lt;t0^*&gt; | &lt;t0^ 1&gt; | 0:0[t0^*]1:0</egg-dsd:structure>
+        </annotation>
+      </species>
+      <species id="t0_d1" compartment="solution" initialConcentration="1e-6" hasOnlySubstanceUnits="false" boundaryCondition="false" constant="false">
+        <annotation>
+          <egg-dsd:structure xmlns:egg-dsd="https://github.com/gussmith23/egg-dsd">&lt;t0^ 1&gt;</egg-dsd:structure>
+        </annotation>
+      </species>
+    </listOfSpecies>
+    <listOfReactions>
+      <reaction id="r0" name="toehold-bind" reversible="false">
+        <listOfReactants>
+          <speciesReference species="t0_0" stoichiometry="1" constant="true"/>
+          <speciesReference species="t0_d1" stoichiometry="1" constant="true"/>
+        </listOfReactants>
+        <listOfProducts>
+          <speciesReference species="complex_2_t0__0_t0__d1_p0_0_1_0_1" stoichiometry="1" constant="true"/>
+        </listOfProducts>
+        <kineticLaw>
+          <math xmlns="http://www.w3.org/1998/Math/MathML">
+            <apply>
+              <times/>
+              <ci> k </ci>
+              <ci> solution </ci>
+              <ci> t0_0 </ci>
+              <ci> t0_d1 </ci>
+            </apply>
+          </math>
+          <listOfLocalParameters>
+            <localParameter id="k" value="3e6"/>
+          </listOfLocalParameters>
+        </kineticLaw>
+      </reaction>
+      <reaction id="r1" name="toehold-dissociation" reversible="false">
+        <listOfReactants>
+          <speciesReference species="complex_2_t0__0_t0__d1_p0_0_1_0_1" stoichiometry="1" constant="true"/>
+        </listOfReactants>
+        <listOfProducts>
+          <speciesReference species="t0_0" stoichiometry="1" constant="true"/>
+          <speciesReference species="t0_d1" stoichiometry="1" constant="true"/>
+        </listOfProducts>
+        <kineticLaw>
+          <math xmlns="http://www.w3.org/1998/Math/MathML">
+            <apply>
+              <times/>
+              <ci> k </ci>
+              <ci> solution </ci>
+              <ci> complex_2_t0__0_t0__d1_p0_0_1_0_1 </ci>
+            </apply>
+          </math>
+          <listOfLocalParameters>
+            <localParameter id="k" value="{:e}"/>
+          </listOfLocalParameters>
+        </kineticLaw>
+      </reaction>
+    </listOfReactions>
+  </model>
+</sbml>
+"#,
+                reactions[1].rate.unwrap()
+            )
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Concrete DNA sequences for abstract domains.
+
+use super::*;
+use std::collections::HashMap;
+
+/// The Watson-Crick complement of a nucleotide (A, C, G or T, in upper
+/// case), or None for anything else.
+pub fn complement_nucleotide(nucleotide: char) -> Option<char> {
+    match nucleotide {
+        'A' => Some('T'),
+        'T' => Some('A'),
+        'C' => Some('G'),
+        'G' => Some('C'),
+        _ => None,
+    }
+}
+
+/// The reverse complement of a nucleotide sequence, or None if it has
+/// anything but A, C, G and T in it.
+pub fn reverse_complement(sequence: &str) -> Option<String> {
+    sequence.chars().rev().map(complement_nucleotide).collect()
+}
+
+/// The length of the longest stretch of `a` which could pair with part of `b`,
+/// i.e. the longest common substring of `a` and the reverse complement of `b`.
+/// Anything but A, C, G and T pairs with nothing.
+pub fn longest_complementarity(a: &str, b: &str) -> usize {
+    let b: String = b.chars().rev().collect();
+    longest_match(a, &b, |a: char, b: char| {
+        complement_nucleotide(b) == Some(a)
+    })
+}
+
+/// The length of the longest common substring of `a` and `b`, i.e. the
+/// longest stretch of `a` which could pair with part of the reverse complement
+/// of `b`.
+pub fn longest_common_substring(a: &str, b: &str) -> usize {
+    longest_match(a, b, |a: char, b: char| a == b)
+}
+
+/// The length of the longest pair of stretches of `a` and `b` in which each
+/// character of `a` matches the character of `b` in the same place.
+fn longest_match(a: &str, b: &str, matches: impl Fn(char, char) -> bool) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut longest: usize = 0;
+    // Lengths of the matching suffixes ending at a[i - 1] and b[j - 1].
+    let mut previous: Vec<usize> = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        let mut current: Vec<usize> = vec![0; b.len() + 1];
+        for j in 1..=b.len() {
+            if matches(a[i - 1], b[j - 1]) {
+                current[j] = previous[j - 1] + 1;
+                longest = std::cmp::max(longest, current[j]);
+            }
+        }
+        previous = current;
+    }
+    longest
+}
+
+/// A DNA sequence for each domain id. Sequences are stored for the domains
+/// themselves; a complement domain takes the reverse complement, and a
+/// complement of a complement takes the original sequence again, just as
+/// Meta::make() collapses double complements.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SequenceAssignment {
+    sequences: HashMap<DomainId, String>,
+}
+
+impl SequenceAssignment {
+    /// Assigns `sequence` (made up of A, C, G and T, in either case, 5' to 3')
+    /// to domain `domain_id`. Assigning to a complement assigns the reverse
+    /// complement to the domain itself.
+    pub fn set(&mut self, domain_id: &DomainId, sequence: &str) -> Result<(), String> {
+        let sequence: String = sequence.to_uppercase();
+        if let Some(c) = sequence.chars().find(|c: &char| !"ACGT".contains(*c)) {
+            return Err(format!("{} isn't a nucleotide", c));
+        }
+        let sequence: String = if domain_id.is_complement() {
+            reverse_complement(&sequence).unwrap()
+        } else {
+            sequence
+        };
+        self.sequences.insert(domain_id.base(), sequence);
+        Ok(())
+    }
+
+    /// The ids with sequences, in order.
+    pub fn domain_ids(&self) -> Vec<DomainId> {
+        let mut domain_ids: Vec<DomainId> = self.sequences.keys().cloned().collect();
+        domain_ids.sort();
+        domain_ids
+    }
+
+    pub fn domain_id_sequence(&self, domain_id: &DomainId) -> Option<String> {
+        match domain_id {
+            DomainId::Complement(domain_id) => self
+                .domain_id_sequence(domain_id)
+                .and_then(|sequence: String| reverse_complement(&sequence)),
+            DomainId::DomainId(_) | DomainId::Named(_) => self.sequences.get(domain_id).cloned(),
+        }
+    }
+
+    pub fn domain_sequence(&self, domain: &Domain) -> Option<String> {
+        match domain {
+            Domain::Toehold(domain_id, _) | Domain::Long(domain_id, _) => {
+                self.domain_id_sequence(domain_id)
+            }
+        }
+    }
+
+    /// The full sequence of a strand made up of `domains`, or None if any
+    /// domain hasn't been given a sequence.
+    pub fn strand_sequence(&self, domains: &[Domain]) -> Option<String> {
+        domains
+            .iter()
+            .map(|domain: &Domain| self.domain_sequence(domain))
+            .collect()
+    }
+
+    /// The sequence of each strand in a species: the strand itself for a
+    /// strand cell, both strands of a double strand and every member strand of
+    /// a complex.
+    pub fn value_sequences(&self, value: &Value) -> Option<Vec<String>> {
+        match value {
+            Value::StrandCellValue(domains) | Value::StrandValue { domains, .. } => {
+                Some(vec![self.strand_sequence(domains)?])
+            }
+            Value::DoubleStrandCellValue(bottom) => Some(vec![
+                self.strand_sequence(bottom)?,
+                self.strand_sequence(&complementary_strand(bottom))?,
+            ]),
+            Value::ComplexValue { strands, .. } => strands
+                .iter()
+                .map(|strand: &Vec<Domain>| self.strand_sequence(strand))
+                .collect(),
+            Value::DomainIdValue(domain_id) => Some(vec![self.domain_id_sequence(domain_id)?]),
+            Value::DomainValue(domain) => Some(vec![self.domain_sequence(domain)?]),
+            Value::StrandIdValue(_)
+            | Value::LengthValue(_)
+            | Value::LocationValue(_)
+            | Value::PairingValue(_)
+            | Value::RuleNameValue(_)
+            | Value::ReactionSideValue(_)
+            | Value::ReactionValue { .. } => None,
+        }
+    }
+
+    /// `domain`, as long as its assigned sequence (if it has one), e.g. to
+    /// rate reactions with a kinetics::RateModel.
+    pub fn with_sequence_length(&self, domain: &Domain) -> Domain {
+        match self.domain_sequence(domain) {
+            Some(sequence) => domain.with_length(sequence.len()),
+            None => domain.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strand_sequences() {
+        let mut sequences = SequenceAssignment::default();
+        sequences.set(&DomainId::DomainId(0), "tctcca").unwrap();
+        sequences.set(&DomainId::DomainId(1), "ACGTTGCA").unwrap();
+        assert!(sequences.set(&DomainId::DomainId(2), "ACGU").is_err());
+        let x: DomainId = DomainId::Named(Name::new("x"));
+        sequences.set(&x.complement(), "AACG").unwrap();
+        assert_eq!(sequences.domain_id_sequence(&x), Some("CGTT".to_string()));
+
+        assert_eq!(
+            sequences.strand_sequence(&parse_domains("t0^ 1").unwrap()),
+            Some("TCTCCAACGTTGCA".to_string())
+        );
+        assert_eq!(
+            sequences.strand_sequence(&parse_domains("1* t0^*").unwrap()),
+            Some("TGCAACGTTGGAGA".to_string())
+        );
+        assert_eq!(
+            sequences.domain_id_sequence(&DomainId::Complement(Box::new(DomainId::Complement(
+                Box::new(DomainId::DomainId(0))
+            )))),
+            Some("TCTCCA".to_string())
+        );
+        assert_eq!(
+            sequences.strand_sequence(&parse_domains("t0^ 2").unwrap()),
+            None
+        );
+        assert_eq!(
+            sequences.value_sequences(&Value::DoubleStrandCellValue(parse_domains("t0^").unwrap())),
+            Some(vec!["TCTCCA".to_string(), "TGGAGA".to_string()])
+        );
+        assert_eq!(
+            sequences.with_sequence_length(&parse_domains("1").unwrap()[0]),
+            parse_domains("1:8").unwrap()[0]
+        );
+    }
+
+    #[test]
+    fn complements() {
+        assert_eq!(complement_nucleotide('G'), Some('C'));
+        assert_eq!(complement_nucleotide('U'), None);
+        assert_eq!(reverse_complement("ACCT"), Some("AGGT".to_string()));
+        assert_eq!(reverse_complement("ACNT"), None);
+        assert_eq!(longest_complementarity("AACCGG", "CGGTTT"), 5);
+        assert_eq!(longest_complementarity("AANNAA", "TTNNTT"), 2);
+        assert_eq!(longest_common_substring("AACCGG", "TCCGA"), 3);
+    }
+}
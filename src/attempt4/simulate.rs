@@ -0,0 +1,826 @@
+use super::enumerate::{species, Reaction};
+use super::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Concentrations (or counts) of each species over time, as produced by the
+/// simulators in this module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeSeries {
+    pub species: Vec<Value>,
+    pub times: Vec<f64>,
+    /// One entry per time in `times`, each holding one value per species in
+    /// `species`.
+    pub values: Vec<Vec<f64>>,
+}
+
+impl TimeSeries {
+    /// The values taken on by `species` over time.
+    pub fn of(&self, species: &Value) -> Option<Vec<f64>> {
+        let index: usize = self.species.iter().position(|s: &Value| s == species)?;
+        Some(self.values.iter().map(|v: &Vec<f64>| v[index]).collect())
+    }
+
+    /// The values at each of `times`, i.e. the last values recorded at or
+    /// before each one. Stochastic runs record values at different times, so
+    /// this puts them on a common footing for comparison.
+    pub fn sample(&self, times: &[f64]) -> TimeSeries {
+        TimeSeries {
+            species: self.species.clone(),
+            times: times.to_vec(),
+            values: times
+                .iter()
+                .map(|time: &f64| {
+                    let index: usize = self
+                        .times
+                        .iter()
+                        .rposition(|t: &f64| t <= time)
+                        .unwrap_or(0);
+                    self.values[index].clone()
+                })
+                .collect(),
+        }
+    }
+
+    /// The last value taken on by `species`.
+    pub fn last(&self, species: &Value) -> Option<f64> {
+        self.of(species)
+            .and_then(|values: Vec<f64>| values.last().cloned())
+    }
+}
+
+/// Why a simulation couldn't be run to the end.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimulationError {
+    /// A reaction without a rate constant; see kinetics::RateModel.
+    MissingRate(Reaction),
+    /// The integrator took `OdeOptions::max_steps` steps without reaching the
+    /// end time.
+    TooManySteps { time: f64 },
+    /// `SsaOptions::max_events` reactions fired without reaching the end
+    /// time; `time` is when the last of them fired.
+    TooManyEvents { time: f64 },
+    /// The explicit integrator found the system to be stiff at `time`, and
+    /// would crawl along with tiny steps from there; use
+    /// OdeMethod::Rosenbrock instead.
+    Stiff { time: f64 },
+}
+
+impl std::fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SimulationError::MissingRate(reaction) => write!(
+                f,
+                "Reaction {:?} has no rate; see kinetics::RateModel",
+                reaction
+            ),
+            SimulationError::TooManySteps { time } => {
+                write!(f, "Gave up after too many steps at t={}", time)
+            }
+            SimulationError::TooManyEvents { time } => {
+                write!(f, "Gave up after too many reactions at t={}", time)
+            }
+            SimulationError::Stiff { time } => write!(f, "The system became stiff at t={}", time),
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+/// How simulate_ode() integrates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OdeMethod {
+    /// The linearly implicit Rosenbrock 2(3) method of Shampine and Reichelt
+    /// (as in MATLAB's ode23s), which is L-stable, so it takes large steps
+    /// through stiff systems, such as those mixing fast toehold binding with
+    /// slow leak reactions. Each step solves a linear system the size of the
+    /// number of species.
+    Rosenbrock,
+    /// The explicit Dormand-Prince Runge-Kutta 4(5) method, which is cheaper
+    /// per step and more accurate on non-stiff systems, but stalls on stiff
+    /// ones; it returns SimulationError::Stiff when it detects that.
+    DormandPrince,
+}
+
+/// Settings for simulate_ode().
+#[derive(Debug, Clone, PartialEq)]
+pub struct OdeOptions {
+    pub method: OdeMethod,
+    pub relative_tolerance: f64,
+    pub absolute_tolerance: f64,
+    pub initial_step: f64,
+    /// Gives up after taking this many steps, accepted or not.
+    pub max_steps: usize,
+}
+
+impl Default for OdeOptions {
+    fn default() -> Self {
+        OdeOptions {
+            method: OdeMethod::Rosenbrock,
+            relative_tolerance: 1e-6,
+            absolute_tolerance: 1e-12,
+            initial_step: 1e-6,
+            max_steps: 1_000_000,
+        }
+    }
+}
+
+/// The species of `reactions`, along with any others only given initial
+/// values, and the initial value (concentration or count) of each.
+fn initial_state<T: Copy + std::iter::Sum<T>>(
+    reactions: &[Reaction],
+    initial: &[(Value, T)],
+) -> (Vec<Value>, Vec<T>) {
+    let mut all_species: Vec<Value> = species(reactions);
+    for (value, _) in initial {
+        if !all_species.contains(value) {
+            all_species.push(value.clone());
+        }
+    }
+    let state: Vec<T> = all_species
+        .iter()
+        .map(|s: &Value| {
+            initial
+                .iter()
+                .filter(|(value, _)| value == s)
+                .map(|(_, amount)| *amount)
+                .sum()
+        })
+        .collect();
+    (all_species, state)
+}
+
+/// A reaction as its rate, and the indices of its reactants and products in
+/// the list of all species.
+type IndexedReaction = (f64, Vec<usize>, Vec<usize>);
+
+/// Each reaction as (rate, reactant indices, product indices) into
+/// `all_species`, which must hold every reactant and product.
+fn indexed_reactions(
+    reactions: &[Reaction],
+    all_species: &[Value],
+) -> Result<Vec<IndexedReaction>, SimulationError> {
+    let index = |value: &Value| -> usize {
+        all_species
+            .iter()
+            .position(|s: &Value| s == value)
+            .unwrap_or_else(|| unreachable!("initial_state() lists every species"))
+    };
+    reactions
+        .iter()
+        .map(|reaction: &Reaction| {
+            Ok((
+                reaction
+                    .rate
+                    .ok_or_else(|| SimulationError::MissingRate(reaction.clone()))?,
+                reaction.reactants.iter().map(index).collect(),
+                reaction.products.iter().map(index).collect(),
+            ))
+        })
+        .collect()
+}
+
+/// The rate of change of each species under mass-action kinetics.
+fn derivative(indexed: &[IndexedReaction], state: &[f64]) -> Vec<f64> {
+    let mut out: Vec<f64> = vec![0.0; state.len()];
+    for (rate, reactants, products) in indexed.iter() {
+        let flux: f64 = reactants
+            .iter()
+            .fold(*rate, |flux: f64, i: &usize| flux * state[*i]);
+        for i in reactants {
+            out[*i] -= flux;
+        }
+        for i in products {
+            out[*i] += flux;
+        }
+    }
+    out
+}
+
+/// The Jacobian of derivative(), as rows: `jacobian[i][j]` is how the rate of
+/// change of species i depends on the amount of species j.
+fn jacobian(indexed: &[IndexedReaction], state: &[f64]) -> Vec<Vec<f64>> {
+    let mut out: Vec<Vec<f64>> = vec![vec![0.0; state.len()]; state.len()];
+    for (rate, reactants, products) in indexed.iter() {
+        for (p, j) in reactants.iter().enumerate() {
+            // The flux, differentiated by the p'th reactant.
+            let partial: f64 = reactants
+                .iter()
+                .enumerate()
+                .filter(|(q, _): &(usize, &usize)| *q != p)
+                .fold(*rate, |partial: f64, (_, i): (usize, &usize)| {
+                    partial * state[*i]
+                });
+            for i in reactants {
+                out[*i][*j] -= partial;
+            }
+            for i in products {
+                out[*i][*j] += partial;
+            }
+        }
+    }
+    out
+}
+
+/// Solves `matrix x = rhs` by Gaussian elimination with partial pivoting, or
+/// returns None if the matrix is singular.
+fn solve(mut matrix: Vec<Vec<f64>>, mut rhs: Vec<f64>) -> Option<Vec<f64>> {
+    let n: usize = rhs.len();
+    for column in 0..n {
+        let pivot: usize = (column..n).max_by(|a: &usize, b: &usize| {
+            matrix[*a][column]
+                .abs()
+                .partial_cmp(&matrix[*b][column].abs())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })?;
+        if matrix[pivot][column] == 0.0 || !matrix[pivot][column].is_finite() {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        rhs.swap(column, pivot);
+        let pivot_row: Vec<f64> = matrix[column].clone();
+        for row in column + 1..n {
+            let factor: f64 = matrix[row][column] / pivot_row[column];
+            if factor == 0.0 {
+                continue;
+            }
+            for (x, p) in matrix[row][column..].iter_mut().zip(&pivot_row[column..]) {
+                *x -= factor * p;
+            }
+            rhs[row] -= factor * rhs[column];
+        }
+    }
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k: usize| matrix[row][k] * rhs[k]).sum();
+        rhs[row] = (rhs[row] - sum) / matrix[row][row];
+    }
+    Some(rhs)
+}
+
+/// Root mean square of `error`, relative to the tolerances in `options` at
+/// the larger of `state` and `next`.
+fn error_norm(error: &[f64], state: &[f64], next: &[f64], options: &OdeOptions) -> f64 {
+    ((0..state.len())
+        .map(|i: usize| {
+            let scale: f64 = options.absolute_tolerance
+                + options.relative_tolerance * state[i].abs().max(next[i].abs());
+            (error[i] / scale).powi(2)
+        })
+        .sum::<f64>()
+        / std::cmp::max(state.len(), 1) as f64)
+        .sqrt()
+}
+
+/// One step of an embedded method: the next state and its estimated local
+/// error, or None if the step couldn't be taken at all and should be retried
+/// with a smaller one.
+type Step = Option<(Vec<f64>, Vec<f64>)>;
+
+/// A Rosenbrock 2(3) step of size `step`, following Shampine and Reichelt,
+/// "The MATLAB ODE Suite" (1997), for an autonomous system.
+fn rosenbrock_step(indexed: &[IndexedReaction], state: &[f64], step: f64) -> Step {
+    let n: usize = state.len();
+    let d: f64 = 1.0 / (2.0 + 2.0f64.sqrt());
+    let e32: f64 = 6.0 + 2.0f64.sqrt();
+
+    // W = I - h d J.
+    let w: Vec<Vec<f64>> = jacobian(indexed, state)
+        .into_iter()
+        .enumerate()
+        .map(|(i, row): (usize, Vec<f64>)| {
+            row.into_iter()
+                .enumerate()
+                .map(|(j, x): (usize, f64)| (if i == j { 1.0 } else { 0.0 }) - step * d * x)
+                .collect()
+        })
+        .collect();
+
+    let f0: Vec<f64> = derivative(indexed, state);
+    let k1: Vec<f64> = solve(w.clone(), f0.clone())?;
+    let f1: Vec<f64> = derivative(
+        indexed,
+        &(0..n)
+            .map(|i: usize| state[i] + 0.5 * step * k1[i])
+            .collect::<Vec<f64>>(),
+    );
+    let k2: Vec<f64> = solve(w.clone(), (0..n).map(|i: usize| f1[i] - k1[i]).collect())?
+        .into_iter()
+        .zip(k1.iter())
+        .map(|(k, k1): (f64, &f64)| k + k1)
+        .collect();
+    let next: Vec<f64> = (0..n).map(|i: usize| state[i] + step * k2[i]).collect();
+    let f2: Vec<f64> = derivative(indexed, &next);
+    let k3: Vec<f64> = solve(
+        w,
+        (0..n)
+            .map(|i: usize| f2[i] - e32 * (k2[i] - f1[i]) - 2.0 * (k1[i] - f0[i]))
+            .collect(),
+    )?;
+    let error: Vec<f64> = (0..n)
+        .map(|i: usize| step / 6.0 * (k1[i] - 2.0 * k2[i] + k3[i]))
+        .collect();
+    if next
+        .iter()
+        .chain(error.iter())
+        .any(|x: &f64| !x.is_finite())
+    {
+        return None;
+    }
+    Some((next, error))
+}
+
+/// Dormand-Prince coefficients. Mass-action kinetics don't depend on time, so
+/// we don't need the nodes.
+const DORMAND_PRINCE_A: [[f64; 6]; 7] = [
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0],
+    [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0],
+    [
+        19372.0 / 6561.0,
+        -25360.0 / 2187.0,
+        64448.0 / 6561.0,
+        -212.0 / 729.0,
+        0.0,
+        0.0,
+    ],
+    [
+        9017.0 / 3168.0,
+        -355.0 / 33.0,
+        46732.0 / 5247.0,
+        49.0 / 176.0,
+        -5103.0 / 18656.0,
+        0.0,
+    ],
+    [
+        35.0 / 384.0,
+        0.0,
+        500.0 / 1113.0,
+        125.0 / 192.0,
+        -2187.0 / 6784.0,
+        11.0 / 84.0,
+    ],
+];
+
+/// Fifth order weights are the last row of DORMAND_PRINCE_A; these are fourth
+/// order.
+const DORMAND_PRINCE_B4: [f64; 7] = [
+    5179.0 / 57600.0,
+    0.0,
+    7571.0 / 16695.0,
+    393.0 / 640.0,
+    -92097.0 / 339200.0,
+    187.0 / 2100.0,
+    1.0 / 40.0,
+];
+
+/// A Dormand-Prince step of size `step`, along with an estimate of the
+/// stiffness ratio: `step` times the largest eigenvalue of the Jacobian, as
+/// Hairer and Wanner estimate it from the last two stages. Explicit methods
+/// are only stable while this is small.
+fn dormand_prince_step(indexed: &[IndexedReaction], state: &[f64], step: f64) -> (Step, f64) {
+    let n: usize = state.len();
+    let mut k: Vec<Vec<f64>> = Vec::with_capacity(7);
+    let mut stage_states: Vec<Vec<f64>> = Vec::with_capacity(7);
+    for stage in 0..7 {
+        let stage_state: Vec<f64> = (0..n)
+            .map(|i: usize| {
+                state[i]
+                    + step
+                        * (0..stage)
+                            .map(|j: usize| DORMAND_PRINCE_A[stage][j] * k[j][i])
+                            .sum::<f64>()
+            })
+            .collect();
+        k.push(derivative(indexed, &stage_state));
+        stage_states.push(stage_state);
+    }
+
+    // The last stage is evaluated at the fifth order solution.
+    let fifth_order: Vec<f64> = stage_states[6].clone();
+    let fourth_order: Vec<f64> = (0..n)
+        .map(|i: usize| {
+            state[i]
+                + step
+                    * (0..7)
+                        .map(|j: usize| DORMAND_PRINCE_B4[j] * k[j][i])
+                        .sum::<f64>()
+        })
+        .collect();
+    let error: Vec<f64> = (0..n)
+        .map(|i: usize| fifth_order[i] - fourth_order[i])
+        .collect();
+
+    let distance = |a: &[f64], b: &[f64]| -> f64 {
+        a.iter()
+            .zip(b.iter())
+            .map(|(a, b): (&f64, &f64)| (a - b).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    };
+    let state_distance: f64 = distance(&stage_states[6], &stage_states[5]);
+    let stiffness: f64 = if state_distance > 0.0 {
+        step * distance(&k[6], &k[5]) / state_distance
+    } else {
+        0.0
+    };
+
+    if fifth_order
+        .iter()
+        .chain(error.iter())
+        .any(|x: &f64| !x.is_finite())
+    {
+        return (None, stiffness);
+    }
+    (Some((fifth_order, error)), stiffness)
+}
+
+/// Integrates the mass-action ODEs of `reactions` from the concentrations in
+/// `initial` (in M; species not listed start at zero) up to `end_time` (in s).
+///
+/// The step size adapts to keep the local error within the tolerances in
+/// `options`, and every accepted step is recorded. DSD rate constants span
+/// many orders of magnitude, which makes most networks stiff, so this uses a
+/// Rosenbrock method unless `options.method` says otherwise.
+pub fn simulate_ode(
+    reactions: &[Reaction],
+    initial: &[(Value, f64)],
+    end_time: f64,
+    options: &OdeOptions,
+) -> Result<TimeSeries, SimulationError> {
+    let (all_species, mut state) = initial_state(reactions, initial);
+    let indexed: Vec<IndexedReaction> = indexed_reactions(reactions, &all_species)?;
+    // The order of the error estimate, for the step size controller.
+    let order: f64 = match options.method {
+        OdeMethod::Rosenbrock => 3.0,
+        OdeMethod::DormandPrince => 5.0,
+    };
+
+    let mut time: f64 = 0.0;
+    let mut step: f64 = options.initial_step.min(end_time);
+    let mut times: Vec<f64> = vec![time];
+    let mut values: Vec<Vec<f64>> = vec![state.clone()];
+    let mut steps: usize = 0;
+    // How many accepted steps have looked stiff, and how many in a row
+    // haven't since the last one that did.
+    let mut stiff_steps: usize = 0;
+    let mut non_stiff_steps: usize = 0;
+    while time < end_time {
+        steps += 1;
+        if steps > options.max_steps {
+            return Err(SimulationError::TooManySteps { time: time });
+        }
+        step = step.min(end_time - time);
+
+        let (result, stiffness): (Step, f64) = match options.method {
+            OdeMethod::Rosenbrock => (rosenbrock_step(&indexed, &state, step), 0.0),
+            OdeMethod::DormandPrince => dormand_prince_step(&indexed, &state, step),
+        };
+        let error: f64 = match result.as_ref() {
+            Some((next, error)) => error_norm(error, &state, next, options),
+            None => f64::INFINITY,
+        };
+
+        if error <= 1.0 {
+            time += step;
+            // Concentrations can't go negative; any which do are error.
+            state = result
+                .unwrap()
+                .0
+                .into_iter()
+                .map(|c: f64| c.max(0.0))
+                .collect();
+            times.push(time);
+            values.push(state.clone());
+
+            // Dormand-Prince is stable up to a stiffness ratio of about 3.3;
+            // if steps keep being limited by that rather than by accuracy,
+            // the system is stiff (Hairer and Wanner's test).
+            if stiffness > 3.25 {
+                non_stiff_steps = 0;
+                stiff_steps += 1;
+                if stiff_steps >= 15 {
+                    return Err(SimulationError::Stiff { time: time });
+                }
+            } else {
+                non_stiff_steps += 1;
+                if non_stiff_steps >= 6 {
+                    stiff_steps = 0;
+                }
+            }
+        }
+
+        // Standard step size controller, with a safety factor and limits on
+        // how quickly the step can change.
+        let factor: f64 = if error == 0.0 {
+            5.0
+        } else if error.is_infinite() {
+            0.2
+        } else {
+            (0.9 * error.powf(-1.0 / order)).max(0.2).min(5.0)
+        };
+        step *= factor;
+    }
+
+    Ok(TimeSeries {
+        species: all_species,
+        times: times,
+        values: values,
+    })
+}
+
+/// Settings for simulate_ssa().
+#[derive(Debug, Clone, PartialEq)]
+pub struct SsaOptions {
+    /// The volume of the reaction, in litres, which relates rate constants
+    /// in /M/s to the propensities of individual molecules.
+    pub volume: f64,
+    /// Seed for the random number generator; runs with the same seed give the
+    /// same results, on any platform and with any version of rand.
+    pub seed: u64,
+    /// Gives up after this many reactions have fired.
+    pub max_events: usize,
+}
+
+impl Default for SsaOptions {
+    fn default() -> Self {
+        SsaOptions {
+            // A femtolitre, in which 1nM is roughly one molecule.
+            volume: 1e-15,
+            seed: 0,
+            max_events: 10_000_000,
+        }
+    }
+}
+
+const AVOGADRO: f64 = 6.022_140_76e23;
+
+/// Simulates `reactions` exactly, with Gillespie's stochastic simulation
+/// algorithm (the direct method), from the molecule counts in `initial`
+/// (species not listed start at zero) up to `end_time` (in s).
+///
+/// Counts are recorded after every reaction that fires, and at `end_time`.
+/// Returns SimulationError::TooManyEvents if more than `options.max_events`
+/// reactions would fire before `end_time`.
+pub fn simulate_ssa(
+    reactions: &[Reaction],
+    initial: &[(Value, u64)],
+    end_time: f64,
+    options: &SsaOptions,
+) -> Result<TimeSeries, SimulationError> {
+    let (all_species, mut counts): (Vec<Value>, Vec<u64>) = initial_state(reactions, initial);
+    let indexed: Vec<IndexedReaction> = indexed_reactions(reactions, &all_species)?;
+
+    // A reaction of order n between molecules has a stochastic rate constant
+    // of k/(N_A V)^(n-1).
+    let molar_volume: f64 = AVOGADRO * options.volume;
+    let propensity = |counts: &[u64], (rate, reactants, _): &IndexedReaction| {
+        let mut available: Vec<u64> = counts.to_vec();
+        let mut propensity: f64 = *rate / molar_volume.powi(reactants.len() as i32 - 1);
+        for i in reactants {
+            propensity *= available[*i] as f64;
+            available[*i] = available[*i].saturating_sub(1);
+        }
+        propensity
+    };
+    let recorded =
+        |counts: &[u64]| -> Vec<f64> { counts.iter().map(|count: &u64| *count as f64).collect() };
+
+    let mut rng = ChaCha8Rng::seed_from_u64(options.seed);
+    let mut time: f64 = 0.0;
+    let mut times: Vec<f64> = vec![time];
+    let mut values: Vec<Vec<f64>> = vec![recorded(&counts)];
+    let mut events: usize = 0;
+    loop {
+        let propensities: Vec<f64> = indexed
+            .iter()
+            .map(|reaction: &IndexedReaction| propensity(&counts, reaction))
+            .collect();
+        let total: f64 = propensities.iter().sum();
+        if total <= 0.0 {
+            break;
+        }
+
+        // Time to the next reaction is exponentially distributed.
+        let next_time: f64 = time - (1.0 - rng.gen::<f64>()).ln() / total;
+        if next_time > end_time {
+            break;
+        }
+        if events == options.max_events {
+            return Err(SimulationError::TooManyEvents { time: time });
+        }
+        events += 1;
+        time = next_time;
+
+        let mut choice: f64 = rng.gen::<f64>() * total;
+        let chosen: usize = propensities
+            .iter()
+            .position(|p: &f64| {
+                choice -= p;
+                choice < 0.0
+            })
+            .unwrap_or(propensities.len() - 1);
+        let (_, reactants, products) = &indexed[chosen];
+        for i in reactants {
+            counts[*i] -= 1;
+        }
+        for i in products {
+            counts[*i] += 1;
+        }
+        times.push(time);
+        values.push(recorded(&counts));
+    }
+
+    times.push(end_time);
+    values.push(recorded(&counts));
+    Ok(TimeSeries {
+        species: all_species,
+        times: times,
+        values: values,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reversible_binding() {
+        let a = Value::StrandCellValue(vec![Domain::toehold(DomainId::DomainId(0))]);
+        let b = Value::StrandCellValue(vec![Domain::toehold(DomainId::Complement(Box::new(
+            DomainId::DomainId(0),
+        )))]);
+        let ab = Value::DoubleStrandCellValue(vec![Domain::toehold(DomainId::DomainId(0))]);
+        let reactions: Vec<Reaction> = vec![
+            Reaction {
+                reactants: vec![a.clone(), b.clone()],
+                products: vec![ab.clone()],
+                rule: "toehold-bind".to_string(),
+                rate: Some(1e6),
+            },
+            Reaction {
+                reactants: vec![ab.clone()],
+                products: vec![a.clone(), b.clone()],
+                rule: "toehold-dissociation".to_string(),
+                rate: Some(1.0),
+            },
+        ];
+
+        // At equilibrium, k_on [a][b] = k_off [ab], and [a] + [ab] = 1uM, so
+        // [a] is the positive root of 1e6 x^2 + x - 1e-6 = 0.
+        let a_eq: f64 = (-1.0 + (1.0f64 + 4.0).sqrt()) / 2e6;
+        // Long after equilibrium, steps are limited by stability rather than
+        // accuracy, which Dormand-Prince counts as stiffness, so only run it
+        // until just after equilibrium.
+        for (method, end_time) in &[
+            (OdeMethod::Rosenbrock, 100.0),
+            (OdeMethod::DormandPrince, 10.0),
+        ] {
+            let time_series: TimeSeries = simulate_ode(
+                &reactions,
+                &[(a.clone(), 1e-6), (b.clone(), 1e-6)],
+                *end_time,
+                &OdeOptions {
+                    method: *method,
+                    ..OdeOptions::default()
+                },
+            )
+            .unwrap();
+
+            let a_final: f64 = time_series.last(&a).unwrap();
+            assert!((a_final - a_eq).abs() < 1e-3 * a_eq);
+            assert!((time_series.last(&ab).unwrap() - (1e-6 - a_eq)).abs() < 1e-3 * a_eq);
+            assert_eq!(time_series.times.last(), Some(end_time));
+        }
+
+        // Without a rate, there's nothing to simulate.
+        let mut unrated: Vec<Reaction> = reactions.clone();
+        unrated[1].rate = None;
+        assert_eq!(
+            simulate_ode(&unrated, &[], 1.0, &OdeOptions::default()),
+            Err(SimulationError::MissingRate(unrated[1].clone()))
+        );
+    }
+
+    #[test]
+    fn stiff_system() {
+        // a converts to b in microseconds and back over hours, and b leaks
+        // slowly to c: the fast reaction forces tiny steps on an explicit
+        // method long after it's reached equilibrium.
+        let a = Value::StrandCellValue(vec![Domain::toehold(DomainId::DomainId(0))]);
+        let b = Value::StrandCellValue(vec![Domain::toehold(DomainId::DomainId(1))]);
+        let c = Value::StrandCellValue(vec![Domain::toehold(DomainId::DomainId(2))]);
+        let reaction = |reactant: &Value, product: &Value, rate: f64| Reaction {
+            reactants: vec![reactant.clone()],
+            products: vec![product.clone()],
+            rule: "leak".to_string(),
+            rate: Some(rate),
+        };
+        let reactions: Vec<Reaction> = vec![
+            reaction(&a, &b, 1e6),
+            reaction(&b, &a, 1e2),
+            reaction(&b, &c, 1e-3),
+        ];
+        let initial: Vec<(Value, f64)> = vec![(a.clone(), 1e-6)];
+
+        let time_series: TimeSeries =
+            simulate_ode(&reactions, &initial, 1e3, &OdeOptions::default()).unwrap();
+        assert!(time_series.times.len() < 1000);
+        // b leaks at 1e-3/s, so about 1 - 1/e of it is gone after 1000s.
+        let c_final: f64 = time_series.last(&c).unwrap();
+        let c_expected: f64 = 1e-6 * (1.0 - (-1e3f64 * 1e-3 * 1e6 / (1e6 + 1e2)).exp());
+        assert!((c_final - c_expected).abs() < 1e-3 * c_expected);
+
+        match simulate_ode(
+            &reactions,
+            &initial,
+            1e3,
+            &OdeOptions {
+                method: OdeMethod::DormandPrince,
+                ..OdeOptions::default()
+            },
+        ) {
+            Err(SimulationError::Stiff { .. }) => (),
+            other => panic!("Expected the system to be found stiff, not {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reproducible_ssa() {
+        let a = Value::StrandCellValue(vec![Domain::toehold(DomainId::DomainId(0))]);
+        let b = Value::StrandCellValue(vec![Domain::toehold(DomainId::Complement(Box::new(
+            DomainId::DomainId(0),
+        )))]);
+        let ab = Value::DoubleStrandCellValue(vec![Domain::toehold(DomainId::DomainId(0))]);
+        let reactions: Vec<Reaction> = vec![Reaction {
+            reactants: vec![a.clone(), b.clone()],
+            products: vec![ab.clone()],
+            rule: "toehold-bind".to_string(),
+            rate: Some(1e6),
+        }];
+        let initial: Vec<(Value, u64)> = vec![(a.clone(), 100), (b.clone(), 60)];
+
+        let run = |seed: u64| {
+            simulate_ssa(
+                &reactions,
+                &initial,
+                1e6,
+                &SsaOptions {
+                    seed: seed,
+                    ..SsaOptions::default()
+                },
+            )
+            .unwrap()
+        };
+        let first: TimeSeries = run(1);
+
+        // Binding is irreversible, so every b ends up bound.
+        assert_eq!(first.last(&a), Some(40.0));
+        assert_eq!(first.last(&b), Some(0.0));
+        assert_eq!(first.last(&ab), Some(60.0));
+        // Molecules are conserved at every step.
+        let ab_index: usize = first
+            .species
+            .iter()
+            .position(|species: &Value| *species == ab)
+            .unwrap();
+        for values in first.values.iter() {
+            assert_eq!(values.iter().sum::<f64>() + values[ab_index], 160.0);
+        }
+
+        assert_eq!(first, run(1));
+        let times: Vec<f64> = vec![0.0, 0.1, 1.0, 10.0];
+        assert_ne!(first.sample(&times), run(2).sample(&times));
+
+        // Binding all 60 b takes 60 reactions, so running out before then is
+        // an error rather than a truncated result.
+        match simulate_ssa(
+            &reactions,
+            &initial,
+            1e6,
+            &SsaOptions {
+                seed: 1,
+                max_events: 59,
+                ..SsaOptions::default()
+            },
+        ) {
+            Err(SimulationError::TooManyEvents { time }) => {
+                assert_eq!(Some(&time), first.times.get(59))
+            }
+            other => panic!("Expected to run out of events, not {:?}", other),
+        }
+        assert_eq!(
+            simulate_ssa(
+                &reactions,
+                &initial,
+                1e6,
+                &SsaOptions {
+                    seed: 1,
+                    max_events: 60,
+                    ..SsaOptions::default()
+                },
+            ),
+            Ok(first)
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Prints the structure held by an eclass in Peppercorn's kernel notation
+//! (`t0( d1( + ) )`) or, one character per domain, in dot-paren-plus notation
+//! (`((+))`).
+
+use super::*;
+
+/// A secondary structure: strands, and which of their domains pair up, as
+/// (strand index, domain index) positions.
+#[derive(Debug, Clone, PartialEq)]
+struct Structure {
+    strands: Vec<Vec<Domain>>,
+    pairs: Vec<((usize, usize), (usize, usize))>,
+}
+
+impl Structure {
+    /// Pairs the domains of `bottom`, starting at `bottom_start` on strand
+    /// `bottom_strand`, with their complements, which end at `top_end` on
+    /// strand `top_strand`.
+    fn pair(
+        &mut self,
+        bottom_strand: usize,
+        bottom_start: usize,
+        top_strand: usize,
+        top_end: usize,
+        length: usize,
+    ) {
+        for i in 0..length {
+            self.pairs
+                .push(((bottom_strand, bottom_start + i), (top_strand, top_end - i)));
+        }
+    }
+
+    fn partner(&self, position: (usize, usize)) -> Option<(usize, usize)> {
+        self.pairs.iter().find_map(|(a, b)| {
+            if *a == position {
+                Some(*b)
+            } else if *b == position {
+                Some(*a)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Renders each domain with `unpaired`, `opening` or `closing` (depending
+    /// on whether its partner comes before or after it), separating domains
+    /// with `separator` and strands with `+`.
+    fn render(
+        &self,
+        unpaired: impl Fn(&Domain) -> String,
+        opening: impl Fn(&Domain) -> String,
+        closing: impl Fn(&Domain) -> String,
+        separator: &str,
+    ) -> String {
+        self.strands
+            .iter()
+            .enumerate()
+            .map(|(strand_index, strand): (usize, &Vec<Domain>)| {
+                strand
+                    .iter()
+                    .enumerate()
+                    .map(|(domain_index, domain): (usize, &Domain)| {
+                        match self.partner((strand_index, domain_index)) {
+                            None => unpaired(domain),
+                            Some(partner) if partner > (strand_index, domain_index) => {
+                                opening(domain)
+                            }
+                            Some(_) => closing(domain),
+                        }
+                    })
+                    .collect::<Vec<String>>()
+                    .join(separator)
+            })
+            .collect::<Vec<String>>()
+            .join(&format!("{}+{}", separator, separator))
+    }
+}
+
+fn structure(egraph: &EGraph<Language, Meta>, eclass: Id) -> Option<Structure> {
+    match egraph[eclass].metadata.value.as_ref()? {
+        Value::StrandCellValue(domains) | Value::StrandValue { domains, .. } => Some(Structure {
+            strands: vec![domains.clone()],
+            pairs: vec![],
+        }),
+        Value::DoubleStrandCellValue(bottom) => {
+            let mut structure = Structure {
+                strands: vec![bottom.clone(), complementary_strand(bottom)],
+                pairs: vec![],
+            };
+            structure.pair(0, 0, 1, bottom.len() - 1, bottom.len());
+            Some(structure)
+        }
+        Value::ComplexValue { strands, pairings } => {
+            let mut structure = Structure {
+                strands: strands.clone(),
+                pairs: vec![],
+            };
+            for pairing in pairings {
+                structure.pair(
+                    pairing.bottom.strand,
+                    pairing.bottom.domain,
+                    pairing.top.strand,
+                    pairing.top.domain + pairing.domains.len() - 1,
+                    pairing.domains.len(),
+                );
+            }
+            Some(structure)
+        }
+        Value::DomainIdValue(_)
+        | Value::DomainValue(_)
+        | Value::StrandIdValue(_)
+        | Value::LengthValue(_)
+        | Value::LocationValue(_)
+        | Value::PairingValue(_)
+        | Value::RuleNameValue(_)
+        | Value::ReactionSideValue(_)
+        | Value::ReactionValue { .. } => None,
+    }
+}
+
+fn kernel_domain_name(domain: &Domain) -> String {
+    let domain_id: &DomainId = match domain {
+        Domain::Toehold(domain_id, _) | Domain::Long(domain_id, _) => domain_id,
+    };
+    if domain_id.is_complement() {
+        format!("{}*", domain.name())
+    } else {
+        domain.name()
+    }
+}
+
+/// The structure held by `eclass` in Peppercorn's kernel notation, with
+/// toeholds named `t<id>` and long domains `d<id>`, unless they were given
+/// names of their own. Returns None if the eclass doesn't hold a strand,
+/// double strand or complex.
+pub fn kernel(egraph: &EGraph<Language, Meta>, eclass: Id) -> Option<String> {
+    Some(structure(egraph, eclass)?.render(
+        kernel_domain_name,
+        |domain: &Domain| format!("{}(", kernel_domain_name(domain)),
+        |_| ")".to_string(),
+        " ",
+    ))
+}
+
+/// The strands of the structure held by `eclass`, as kernel() lists them:
+/// unlike its value, a hairpin's strand includes its loop. Returns None if the
+/// eclass doesn't hold a strand, double strand or complex.
+pub fn strands(egraph: &EGraph<Language, Meta>, eclass: Id) -> Option<Vec<Vec<Domain>>> {
+    Some(structure(egraph, eclass)?.strands)
+}
+
+/// The structure held by `eclass` in dot-paren-plus notation, with one
+/// character per domain. Returns None if the eclass doesn't hold a strand,
+/// double strand or complex.
+pub fn dot_paren_plus(egraph: &EGraph<Language, Meta>, eclass: Id) -> Option<String> {
+    Some(structure(egraph, eclass)?.render(
+        |_| ".".to_string(),
+        |_| "(".to_string(),
+        |_| ")".to_string(),
+        "",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn print_structures() {
+        let mut egraph = EGraph::<Language, Meta>::default();
+        let strand: Id =
+            add_strand_to_egraph(&mut egraph, &parse_domains("t0^ 1").unwrap()).unwrap();
+        let double_strand: Id =
+            add_double_strand_to_egraph(&mut egraph, &parse_domains("t0^ 1").unwrap()).unwrap();
+        let hairpin: Id = add_hairpin_to_egraph(
+            &mut egraph,
+            &parse_domains("1").unwrap(),
+            &parse_domains("2 3").unwrap(),
+        )
+        .unwrap();
+        let gate: Id = add_complex_to_egraph(
+            &mut egraph,
+            &vec![
+                parse_domains("1 4").unwrap(),
+                parse_domains("1* t0^*").unwrap(),
+            ],
+            &vec![Pairing {
+                bottom: Location {
+                    strand: 0,
+                    domain: 0,
+                },
+                top: Location {
+                    strand: 1,
+                    domain: 0,
+                },
+                domains: parse_domains("1").unwrap(),
+            }],
+        )
+        .unwrap();
+
+        assert_eq!(kernel(&egraph, strand).unwrap(), "t0 d1");
+        assert_eq!(kernel(&egraph, double_strand).unwrap(), "t0( d1( + ) )");
+        assert_eq!(dot_paren_plus(&egraph, double_strand).unwrap(), "((+))");
+        assert_eq!(kernel(&egraph, hairpin).unwrap(), "d1( d2 d3 )");
+        assert_eq!(dot_paren_plus(&egraph, hairpin).unwrap(), "(..)");
+        assert_eq!(kernel(&egraph, gate).unwrap(), "d1*( t0* + ) d4");
+        assert_eq!(dot_paren_plus(&egraph, gate).unwrap(), "(.+).");
+    }
+}
@@ -0,0 +1,246 @@
+//! Domain names, so that domains can be named by string.
+//!
+//! Names are a separate kind of domain id (DomainId::Named) from numbers.
+//! They're interned in a global table, so that a named domain id is as cheap
+//! to copy, compare and hash throughout the egraph as a numbered one. A name's
+//! place in the table is never observable: names are ordered by their text, so
+//! the same name is the same domain in any system, whatever was named before
+//! it.
+//!
+//! Numbered domains are written `t<n>` (toeholds) and `d<n>` (long domains)
+//! by Domain::name(), so names which look like that are escaped with an extra
+//! trailing underscore: the domain named `t1` is written `t1_`, and the one
+//! named `t1_` is written `t1__`.
+
+use super::{DomainId, DomainIdValue};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+/// Words which already mean something in Language, and so can't name domains.
+const RESERVED: &[&str] = &[
+    "nil",
+    "complement",
+    "domain",
+    "complex",
+    "pairing",
+    "strand",
+    "reaction",
+    "reactants",
+    "products",
+];
+
+/// Whether `name` can name a domain: it must start with a letter, contain only
+/// letters, digits and underscores, and not be one of Language's own words.
+pub fn is_valid_name(name: &str) -> bool {
+    match name.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => (),
+        _ => return false,
+    }
+    name.chars()
+        .all(|c: char| c.is_ascii_alphanumeric() || c == '_')
+        && !RESERVED.contains(&name)
+}
+
+/// Every name interned so far. Names are never removed, so each one's text can
+/// be leaked and handed out without holding the lock.
+#[derive(Default)]
+struct Names {
+    indices: HashMap<&'static str, u32>,
+    names: Vec<&'static str>,
+}
+
+fn names() -> &'static Mutex<Names> {
+    static NAMES: OnceLock<Mutex<Names>> = OnceLock::new();
+    NAMES.get_or_init(Mutex::default)
+}
+
+/// An interned domain name. Two Names are equal exactly when their text is,
+/// and they're ordered by their text, not by when they were interned.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Name(u32);
+
+impl Name {
+    /// Interns `name`. This doesn't check that it can name a domain; see
+    /// is_valid_name().
+    pub fn new(name: &str) -> Name {
+        let mut names = names().lock().unwrap();
+        if let Some(index) = names.indices.get(name) {
+            return Name(*index);
+        }
+        let index: u32 = names.names.len() as u32;
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        names.indices.insert(name, index);
+        names.names.push(name);
+        Name(index)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        names().lock().unwrap().names[self.0 as usize]
+    }
+}
+
+impl Ord for Name {
+    fn cmp(&self, other: &Name) -> std::cmp::Ordering {
+        if self == other {
+            std::cmp::Ordering::Equal
+        } else {
+            self.as_str().cmp(other.as_str())
+        }
+    }
+}
+
+impl PartialOrd for Name {
+    fn partial_cmp(&self, other: &Name) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Debug for Name {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+/// Names print unescaped; see escape_name() for how they're written as
+/// domains.
+impl std::fmt::Display for Name {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// If `text` is `t` or `d`, then digits, then any number of underscores,
+/// returns how many underscores it ends with.
+fn numbered_like(text: &str) -> Option<usize> {
+    if !text.starts_with('t') && !text.starts_with('d') {
+        return None;
+    }
+    let rest: &str = &text[1..];
+    let digits: &str = rest.trim_end_matches('_');
+    let trailing: usize = rest.len() - digits.len();
+    if !digits.is_empty() && digits.chars().all(|c: char| c.is_ascii_digit()) {
+        Some(trailing)
+    } else {
+        None
+    }
+}
+
+/// `name` as it's written, escaped so it can't be mistaken for a numbered
+/// domain.
+pub fn escape_name(name: &str) -> String {
+    if numbered_like(name).is_some() {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// A domain id as it's written in Language and in compact notation: a number
+/// (optionally written `t<n>` or `d<n>`) or an escaped name. This is what lets
+/// patterns say `(domain-id rep_out)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Symbol {
+    Number(DomainIdValue),
+    Name(Name),
+}
+
+impl From<Symbol> for DomainId {
+    fn from(symbol: Symbol) -> DomainId {
+        match symbol {
+            Symbol::Number(v) => DomainId::DomainId(v),
+            Symbol::Name(name) => DomainId::Named(name),
+        }
+    }
+}
+
+impl std::str::FromStr for Symbol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(v) = s.parse::<DomainIdValue>() {
+            return Ok(Symbol::Number(v));
+        }
+        match numbered_like(s) {
+            Some(0) => s[1..]
+                .parse()
+                .map(Symbol::Number)
+                .map_err(|_| format!("'{}' can't name a domain", s)),
+            Some(_) => Ok(Symbol::Name(Name::new(&s[..s.len() - 1]))),
+            None if is_valid_name(s) => Ok(Symbol::Name(Name::new(s))),
+            None => Err(format!("'{}' can't name a domain", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Symbol::Number(v) => write!(f, "{}", v),
+            Symbol::Name(name) => write!(f, "{}", escape_name(name.as_str())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Language;
+    use super::*;
+
+    #[test]
+    fn symbols() {
+        assert_eq!("3".parse(), Ok(Symbol::Number(3)));
+        assert_eq!("t3".parse(), Ok(Symbol::Number(3)));
+        assert_eq!("d3".parse(), Ok(Symbol::Number(3)));
+        assert_eq!("rep_out".parse(), Ok(Symbol::Name(Name::new("rep_out"))));
+        assert_eq!(Symbol::Number(3).to_string(), "3");
+        assert_eq!(Symbol::Name(Name::new("rep_out")).to_string(), "rep_out");
+        assert!("nil".parse::<Symbol>().is_err());
+        assert!("strand".parse::<Symbol>().is_err());
+        assert!("rep-out".parse::<Symbol>().is_err());
+        assert!("_x".parse::<Symbol>().is_err());
+
+        // Names which look like numbered domains are escaped, and read back.
+        for name in &["t1", "d1_", "t", "t1x", "x1"] {
+            let symbol = Symbol::Name(Name::new(name));
+            assert_eq!(symbol.to_string().parse(), Ok(symbol));
+        }
+        assert_eq!(Symbol::Name(Name::new("t1")).to_string(), "t1_");
+        assert_ne!(
+            Symbol::Name(Name::new("t1")).to_string(),
+            Symbol::Number(1).to_string()
+        );
+    }
+
+    #[test]
+    fn interned_names() {
+        assert_eq!(Name::new("rep_out"), Name::new("rep_out"));
+        assert_ne!(Name::new("rep_out"), Name::new("rep_in"));
+        assert_eq!(Name::new("rep_out").as_str(), "rep_out");
+        // Names sort by their text, whichever was interned first.
+        assert!(Name::new("zz_interned_first") > Name::new("aa_interned_second"));
+    }
+
+    #[test]
+    fn language_words_are_reserved() {
+        for op in &[
+            Language::DoubleStrandCell,
+            Language::Complex,
+            Language::Pairing,
+            Language::StrandCell,
+            Language::Domain,
+            Language::LongDomain,
+            Language::ToeholdDomain,
+            Language::Complement,
+            Language::DomainId,
+            Language::Nil,
+            Language::Strand,
+            Language::Reaction,
+            Language::Reactants,
+            Language::Products,
+        ] {
+            let word: String = op.to_string();
+            assert!(!is_valid_name(&word), "{} can name a domain", word);
+            assert!(word.parse::<Symbol>().is_err());
+        }
+    }
+}